@@ -0,0 +1,305 @@
+// The `FlatArticle` and `TreeArticle` types in `create_schema`, and conversion between them.
+//
+// A `FlatArticle` is a list of blocks, some of which are headings; a `TreeArticle` has a root
+// section whose subsections are nested by heading level. Conversions create new block and section
+// nodes, but refer to the same text nodes.
+
+use std::collections::BTreeMap;
+
+use crate::{new_id, Node, ObjectValue, Value, D, ID};
+
+pub const FLAT_ARTICLE_TYPE_ID: ID = 298732;
+pub const FLAT_ARTICLE_BLOCKS: ID = 37438;
+
+pub const FLAT_BLOCK_TYPE_ID: ID = 28398;
+pub const FLAT_BLOCK_H1: ID = 29382;
+pub const FLAT_BLOCK_H2: ID = 129382;
+pub const FLAT_BLOCK_H3: ID = 93483;
+pub const FLAT_BLOCK_PARAGRAPH: ID = 238239;
+pub const FLAT_BLOCK_QUOTE: ID = 9834734;
+pub const FLAT_BLOCK_CODE: ID = 389734;
+pub const FLAT_BLOCK_ORDERED_LIST: ID = 834734;
+pub const FLAT_BLOCK_UNORDERED_LIST: ID = 756347;
+
+pub const TREE_ARTICLE_TYPE_ID: ID = 9823923;
+pub const TREE_ARTICLE_SECTIONS: ID = 34837;
+
+pub const SECTION_TYPE_ID: ID = 8734289;
+pub const SECTION_TITLE: ID = 21837;
+pub const SECTION_BLOCKS: ID = 298734;
+pub const SECTION_SECTIONS: ID = 4872361;
+
+pub const TREE_BLOCK_TYPE_ID: ID = 93847373;
+pub const TREE_BLOCK_TEXT: ID = 387439;
+pub const TREE_BLOCK_QUOTE: ID = 38743;
+pub const TREE_BLOCK_CODE: ID = 39843;
+pub const TREE_BLOCK_ORDERED_LIST: ID = 2938471;
+pub const TREE_BLOCK_UNORDERED_LIST: ID = 2938473;
+
+// Heading fields of `FlatArticleBlock`, by level.
+pub const FLAT_BLOCK_HEADINGS: [ID; 3] = [FLAT_BLOCK_H1, FLAT_BLOCK_H2, FLAT_BLOCK_H3];
+
+// Fields of `FlatArticleBlock` and the fields of `TreeArticleBlock` with the same content.
+const BLOCK_FIELDS: &[(ID, ID)] = &[
+    (FLAT_BLOCK_PARAGRAPH, TREE_BLOCK_TEXT),
+    (FLAT_BLOCK_QUOTE, TREE_BLOCK_QUOTE),
+    (FLAT_BLOCK_CODE, TREE_BLOCK_CODE),
+    (FLAT_BLOCK_ORDERED_LIST, TREE_BLOCK_ORDERED_LIST),
+    (FLAT_BLOCK_UNORDERED_LIST, TREE_BLOCK_UNORDERED_LIST),
+];
+
+fn put_object<P: FnMut(Node) -> D>(
+    put: &mut P,
+    object_type_id: ID,
+    fields: BTreeMap<ID, Vec<D>>,
+) -> D {
+    put(Node {
+        id: new_id(),
+        value: Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        }),
+        schema: None,
+    })
+}
+
+fn get_object<G: Fn(&D) -> Option<Node>>(get_node: &G, digest: &D) -> anyhow::Result<ObjectValue> {
+    match get_node(digest).map(|node| node.value) {
+        Some(Value::Object(object)) => Ok(object),
+        Some(_) => anyhow::bail!("node {} is not an object", digest.to_hex()),
+        None => anyhow::bail!("node {} not found", digest.to_hex()),
+    }
+}
+
+// Builds the sections of a `TreeArticle` from a sequence of headings and blocks.
+pub struct SectionFolder {
+    // The sections that are still open, starting with the root.
+    open: Vec<OpenSection>,
+}
+
+// A section that may still get blocks and subsections.
+#[derive(Default)]
+struct OpenSection {
+    // Level of the heading that started the section; 0 for the root.
+    level: usize,
+    title: Option<D>,
+    blocks: Vec<D>,
+    sections: Vec<D>,
+}
+
+impl SectionFolder {
+    pub fn new() -> Self {
+        SectionFolder {
+            open: vec![OpenSection::default()],
+        }
+    }
+
+    // Starts a section, after closing the open ones at the same or a deeper level.
+    pub fn heading<P: FnMut(Node) -> D>(&mut self, put: &mut P, level: usize, title: Option<D>) {
+        while self.open.len() > 1 && self.open.last().is_some_and(|s| s.level >= level) {
+            self.close(put);
+        }
+        self.open.push(OpenSection {
+            level,
+            title,
+            ..Default::default()
+        });
+    }
+
+    // Adds a `TreeArticleBlock` to the innermost open section.
+    pub fn block(&mut self, block: D) {
+        if let Some(section) = self.open.last_mut() {
+            section.blocks.push(block);
+        }
+    }
+
+    // Closes all sections, and returns the value of the `TreeArticle` with the root section.
+    pub fn finish<P: FnMut(Node) -> D>(mut self, put: &mut P) -> ObjectValue {
+        while self.open.len() > 1 {
+            self.close(put);
+        }
+        let root = section(put, self.open.pop().unwrap_or_default());
+        ObjectValue {
+            object_type_id: TREE_ARTICLE_TYPE_ID,
+            fields: BTreeMap::from([(TREE_ARTICLE_SECTIONS, vec![root])]),
+        }
+    }
+
+    // Adds the innermost open section to its parent.
+    fn close<P: FnMut(Node) -> D>(&mut self, put: &mut P) {
+        let Some(open) = self.open.pop() else {
+            return;
+        };
+        let section = section(put, open);
+        if let Some(parent) = self.open.last_mut() {
+            parent.sections.push(section);
+        }
+    }
+}
+
+fn section<P: FnMut(Node) -> D>(put: &mut P, section: OpenSection) -> D {
+    let mut fields = BTreeMap::new();
+    if let Some(title) = section.title {
+        fields.insert(SECTION_TITLE, vec![title]);
+    }
+    if !section.blocks.is_empty() {
+        fields.insert(SECTION_BLOCKS, section.blocks);
+    }
+    if !section.sections.is_empty() {
+        fields.insert(SECTION_SECTIONS, section.sections);
+    }
+    put_object(put, SECTION_TYPE_ID, fields)
+}
+
+// Heading level at which the title of a `TreeArticle` section at the given depth is written, if
+// any, where the root section is at depth 0 and only has a heading if it has a title.
+pub fn section_heading_level(depth: usize, has_title: bool) -> Option<usize> {
+    (depth > 0 || has_title).then_some(depth.max(1))
+}
+
+// Converts a `FlatArticle` into a `TreeArticle`, where each heading starts a section.
+pub fn flat_to_tree_article<G: Fn(&D) -> Option<Node>, P: FnMut(Node) -> D>(
+    get_node: &G,
+    put: &mut P,
+    flat: &ObjectValue,
+) -> anyhow::Result<ObjectValue> {
+    let mut folder = SectionFolder::new();
+    for digest in flat.fields.get(&FLAT_ARTICLE_BLOCKS).into_iter().flatten() {
+        let block = get_object(get_node, digest)?;
+        let mut heading = false;
+        let mut fields = BTreeMap::new();
+        for (field_id, digests) in &block.fields {
+            if let Some(level) = FLAT_BLOCK_HEADINGS.iter().position(|id| id == field_id) {
+                folder.heading(put, level + 1, digests.first().cloned());
+                heading = true;
+                continue;
+            }
+            let (_, tree_field_id) = BLOCK_FIELDS
+                .iter()
+                .find(|(flat_field_id, _)| flat_field_id == field_id)
+                .ok_or_else(|| anyhow::anyhow!("unknown FlatArticleBlock field {}", field_id))?;
+            fields.insert(*tree_field_id, digests.clone());
+        }
+        // Blocks with only a heading become sections; empty ones are kept.
+        if !(heading && fields.is_empty()) {
+            folder.block(put_object(put, TREE_BLOCK_TYPE_ID, fields));
+        }
+    }
+    Ok(folder.finish(put))
+}
+
+// Converts a `TreeArticle` into a `FlatArticle`, where each section starts with a heading. Sections
+// deeper than the third level get h3 headings, so they become siblings if converted back.
+pub fn tree_to_flat_article<G: Fn(&D) -> Option<Node>, P: FnMut(Node) -> D>(
+    get_node: &G,
+    put: &mut P,
+    tree: &ObjectValue,
+) -> anyhow::Result<ObjectValue> {
+    let mut blocks = vec![];
+    for digest in tree
+        .fields
+        .get(&TREE_ARTICLE_SECTIONS)
+        .into_iter()
+        .flatten()
+    {
+        flatten_section(get_node, put, digest, 0, &mut blocks)?;
+    }
+    Ok(ObjectValue {
+        object_type_id: FLAT_ARTICLE_TYPE_ID,
+        fields: BTreeMap::from([(FLAT_ARTICLE_BLOCKS, blocks)]),
+    })
+}
+
+fn flatten_section<G: Fn(&D) -> Option<Node>, P: FnMut(Node) -> D>(
+    get_node: &G,
+    put: &mut P,
+    digest: &D,
+    depth: usize,
+    blocks: &mut Vec<D>,
+) -> anyhow::Result<()> {
+    let section = get_object(get_node, digest)?;
+    let title = section
+        .fields
+        .get(&SECTION_TITLE)
+        .cloned()
+        .unwrap_or_default();
+    let depth = match section_heading_level(depth, !title.is_empty()) {
+        Some(level) => {
+            let field_id = FLAT_BLOCK_HEADINGS[level.min(3) - 1];
+            blocks.push(put_object(
+                put,
+                FLAT_BLOCK_TYPE_ID,
+                BTreeMap::from([(field_id, title)]),
+            ));
+            level
+        }
+        None => depth,
+    };
+    for digest in section.fields.get(&SECTION_BLOCKS).into_iter().flatten() {
+        let block = get_object(get_node, digest)?;
+        let mut fields = BTreeMap::new();
+        for (field_id, digests) in &block.fields {
+            let (flat_field_id, _) = BLOCK_FIELDS
+                .iter()
+                .find(|(_, tree_field_id)| tree_field_id == field_id)
+                .ok_or_else(|| anyhow::anyhow!("unknown TreeArticleBlock field {}", field_id))?;
+            fields.insert(*flat_field_id, digests.clone());
+        }
+        blocks.push(put_object(put, FLAT_BLOCK_TYPE_ID, fields));
+    }
+    for digest in section.fields.get(&SECTION_SECTIONS).into_iter().flatten() {
+        flatten_section(get_node, put, digest, depth + 1, blocks)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::{import_markdown, HasDigest};
+#[cfg(test)]
+use std::{cell::RefCell, collections::HashMap};
+
+#[test]
+fn test_convert_article() {
+    let nodes = RefCell::new(HashMap::new());
+    let mut put = |node: Node| {
+        let digest = node.digest();
+        nodes.borrow_mut().insert(digest.clone(), node);
+        digest
+    };
+    let get_node = |digest: &D| nodes.borrow().get(digest).cloned();
+    let object = |digest: &D| get_object(&get_node, digest).unwrap();
+
+    let source = "intro\n\n# A\n\ntext\n\n## A.1\n\n- item\n\n# B\n\n> quote\n";
+    let flat_root = import_markdown(source, FLAT_ARTICLE_TYPE_ID, &mut put)
+        .unwrap()
+        .root;
+    let flat = object(&flat_root);
+    let tree = flat_to_tree_article(&get_node, &mut put, &flat).unwrap();
+
+    let flat_blocks: Vec<_> = flat.fields[&FLAT_ARTICLE_BLOCKS]
+        .iter()
+        .map(object)
+        .collect();
+    // The text node of a flat block, which the tree shares.
+    let text = |index: usize| flat_blocks[index].fields.values().next().unwrap()[0].clone();
+    let root = object(&tree.fields[&TREE_ARTICLE_SECTIONS][0]);
+    assert!(!root.fields.contains_key(&SECTION_TITLE));
+    let intro = object(&root.fields[&SECTION_BLOCKS][0]);
+    assert_eq!(intro.fields[&TREE_BLOCK_TEXT], vec![text(0)]);
+    let a = object(&root.fields[&SECTION_SECTIONS][0]);
+    assert_eq!(a.fields[&SECTION_TITLE], vec![text(1)]);
+    let a1 = object(&a.fields[&SECTION_SECTIONS][0]);
+    assert_eq!(a1.fields[&SECTION_TITLE], vec![text(3)]);
+    let item = object(&a1.fields[&SECTION_BLOCKS][0]);
+    assert_eq!(item.fields[&TREE_BLOCK_UNORDERED_LIST], vec![text(4)]);
+    let b = object(&root.fields[&SECTION_SECTIONS][1]);
+    assert_eq!(b.fields[&SECTION_TITLE], vec![text(5)]);
+
+    // Flattening gives back blocks with the same content.
+    let flattened = tree_to_flat_article(&get_node, &mut put, &tree).unwrap();
+    let flattened_blocks: Vec<_> = flattened.fields[&FLAT_ARTICLE_BLOCKS]
+        .iter()
+        .map(object)
+        .collect();
+    assert_eq!(flattened_blocks, flat_blocks);
+}
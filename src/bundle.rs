@@ -0,0 +1,229 @@
+// A single file holding a set of documents together with every node they reference, so that they
+// can be moved to another store without going through static.space.
+//
+// The first line is a header listing the roots, followed by one line for each node with the
+// digest it is stored under. Nodes are only stored on import once all of them have been checked
+// against their digests, and everything reachable from the roots is present.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{reachable, HasDigest, Imported, Node, D};
+
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct BundleHeader {
+    version: u32,
+    roots: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleEntry {
+    digest: String,
+    node: Node,
+}
+
+// Writes the given roots and all the nodes reachable from them, including their schemas.
+pub fn export_bundle<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    roots: &[D],
+) -> anyhow::Result<String> {
+    let header = BundleHeader {
+        version: BUNDLE_VERSION,
+        roots: roots.iter().map(D::to_hex).collect(),
+    };
+    let mut lines = vec![serde_json::to_string(&header)?];
+    let nodes = reachable(roots, |digest| match get_node(digest) {
+        Some(node) => Ok(Some(node)),
+        None => anyhow::bail!("node {} not found", digest.to_hex()),
+    })?;
+    for (digest, node) in nodes {
+        lines.push(serde_json::to_string(&BundleEntry {
+            digest: digest.to_hex(),
+            node,
+        })?);
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+pub struct BundleImport {
+    pub roots: Vec<D>,
+    // Number of nodes that were stored.
+    pub nodes: usize,
+}
+
+impl Imported for BundleImport {
+    // Bundles are checked to have at least one root.
+    fn root(&self) -> &D {
+        &self.roots[0]
+    }
+
+    fn messages(&self) -> Vec<String> {
+        vec![format!("imported {} nodes", self.nodes)]
+    }
+}
+
+// Reads a bundle, passing its nodes to `put` only if all of them match their digests and no node
+// reachable from the roots is missing.
+pub fn import_bundle<P: FnMut(Node) -> D>(
+    source: &str,
+    put: &mut P,
+) -> anyhow::Result<BundleImport> {
+    let mut lines = source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((index, header)) = lines.next() else {
+        anyhow::bail!("empty bundle");
+    };
+    let header_line_number = index + 1;
+    let header: BundleHeader = serde_json::from_str(header)
+        .map_err(|err| anyhow::anyhow!("line {}: invalid header: {}", header_line_number, err))?;
+    if header.version != BUNDLE_VERSION {
+        anyhow::bail!("unsupported bundle version {}", header.version);
+    }
+    let roots = header
+        .roots
+        .iter()
+        .map(|hex| parse_digest(hex, header_line_number))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if roots.is_empty() {
+        anyhow::bail!("bundle has no roots");
+    }
+
+    let mut nodes = HashMap::new();
+    for (index, line) in lines {
+        let line_number = index + 1;
+        let entry: BundleEntry = serde_json::from_str(line)
+            .map_err(|err| anyhow::anyhow!("line {}: invalid node: {}", line_number, err))?;
+        let expected = parse_digest(&entry.digest, line_number)?;
+        let actual = entry.node.digest();
+        if actual != expected {
+            anyhow::bail!(
+                "line {}: digest mismatch: expected {}, found {}",
+                line_number,
+                expected.to_hex(),
+                actual.to_hex()
+            );
+        }
+        nodes.insert(actual, entry.node);
+    }
+
+    // Only the nodes reachable from the roots are stored.
+    let reachable = reachable(&roots, |digest| match nodes.remove(digest) {
+        Some(node) => Ok(Some(node)),
+        None => anyhow::bail!("node {} is missing", digest.to_hex()),
+    })?;
+    let count = reachable.len();
+    for (_, node) in reachable {
+        put(node);
+    }
+    Ok(BundleImport {
+        roots,
+        nodes: count,
+    })
+}
+
+fn parse_digest(hex: &str, line_number: usize) -> anyhow::Result<D> {
+    D::parse_hex(hex)
+        .ok_or_else(|| anyhow::anyhow!("line {}: invalid digest {:?}", line_number, hex))
+}
+
+#[cfg(test)]
+use crate::{create_schema, schema_to_nodes, MemoryStore, ObjectValue, Store, Value};
+#[cfg(test)]
+use maplit::btreemap;
+
+#[cfg(test)]
+fn test_bundle_nodes() -> (D, MemoryStore<Node, D>) {
+    let (schema_digest, schema_nodes) = schema_to_nodes(&create_schema());
+    let store = MemoryStore::new();
+    for node in schema_nodes {
+        store.put(node);
+    }
+    let put = |node: Node| store.put(node);
+    let title = put(Node {
+        id: 1,
+        value: Value::String("Hello".to_string()),
+        schema: None,
+    });
+    let root = put(Node {
+        id: 2,
+        value: Value::Object(ObjectValue {
+            object_type_id: 2325,
+            fields: btreemap! { 0 => vec![title.clone(), title] },
+        }),
+        schema: Some(schema_digest),
+    });
+    // Not reachable from the root.
+    put(Node {
+        id: 3,
+        value: Value::Int(1),
+        schema: None,
+    });
+    (root, store)
+}
+
+#[test]
+fn test_bundle_round_trip() {
+    let (root, store) = test_bundle_nodes();
+    let bundle =
+        export_bundle(&|digest: &D| store.get(digest), std::slice::from_ref(&root)).unwrap();
+    assert!(bundle.starts_with(&format!(
+        "{{\"version\":1,\"roots\":[\"{}\"]}}\n",
+        root.to_hex()
+    )));
+
+    let imported = MemoryStore::new();
+    let import = import_bundle(&bundle, &mut |node: Node| imported.put(node)).unwrap();
+    assert_eq!(import.roots, vec![root]);
+    // Everything but the unreachable node, with the shared title only once.
+    let digests = imported.digests();
+    assert_eq!(import.nodes, store.digests().len() - 1);
+    assert_eq!(digests.len(), import.nodes);
+    assert!(digests
+        .iter()
+        .all(|digest| imported.get(digest) == store.get(digest)));
+}
+
+#[test]
+fn test_bundle_import_errors() {
+    let (root, store) = test_bundle_nodes();
+    let bundle =
+        export_bundle(&|digest: &D| store.get(digest), std::slice::from_ref(&root)).unwrap();
+    let import = |source: &str| {
+        let mut count = 0;
+        let result = import_bundle(source, &mut |node: Node| {
+            count += 1;
+            node.digest()
+        });
+        // Nothing is stored from a bundle that fails verification.
+        assert_eq!(count, 0);
+        result.err().map(|err| err.to_string())
+    };
+
+    let tampered = bundle.replacen("\"Hello\"", "\"Goodbye\"", 1);
+    let line_number = tampered
+        .lines()
+        .position(|line| line.contains("Goodbye"))
+        .unwrap()
+        + 1;
+    assert!(import(&tampered)
+        .unwrap()
+        .starts_with(&format!("line {}: digest mismatch", line_number)));
+
+    let truncated = bundle.lines().take(2).collect::<Vec<_>>().join("\n");
+    assert!(import(&truncated).unwrap().ends_with("is missing"));
+
+    assert_eq!(import(""), Some("empty bundle".to_string()));
+    assert_eq!(
+        import("{\"version\":2,\"roots\":[]}"),
+        Some("unsupported bundle version 2".to_string())
+    );
+    assert_eq!(
+        import("{\"version\":1,\"roots\":[\"sha2-256:00\"]}"),
+        Some("line 1: invalid digest \"sha2-256:00\"".to_string())
+    );
+}
@@ -0,0 +1,775 @@
+// Conversion between Cargo.toml files and nodes of the `CargoManifest` type in `create_schema`, see
+// https://doc.rust-lang.org/cargo/reference/manifest.html
+//
+// Keys are mapped to fields by name, following the field types of the schema.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    new_id, not_imported, FieldType, Imported, Node, ObjectType, ObjectValue, Schema, Type, Value,
+    D, ID,
+};
+
+pub const CARGO_MANIFEST_TYPE_ID: ID = 893728943;
+
+// A table whose entries are keyed by name, e.g. `[dependencies]`, with one object per entry.
+struct KeyedTable {
+    // Name of the object type of the entries.
+    type_name: &'static str,
+    // The field that holds the key.
+    key_field: &'static str,
+    // The field that holds the value of the entry if it is not a table of fields, e.g. the version
+    // requirement in `serde = "1.0"`.
+    value_field: Option<&'static str>,
+    // Whether entries are written inline, e.g. `serde = { version = "1.0" }`, rather than as
+    // sections of their own, e.g. `[profile.release]`.
+    inline: bool,
+}
+
+const KEYED_TABLES: &[KeyedTable] = &[
+    KeyedTable {
+        type_name: "Dependency",
+        key_field: "name",
+        value_field: Some("version"),
+        inline: true,
+    },
+    KeyedTable {
+        type_name: "Feature",
+        key_field: "name",
+        value_field: Some("enables"),
+        inline: true,
+    },
+    KeyedTable {
+        type_name: "Target",
+        key_field: "cfg",
+        value_field: None,
+        inline: false,
+    },
+    KeyedTable {
+        type_name: "Profile",
+        key_field: "name",
+        value_field: None,
+        inline: false,
+    },
+    KeyedTable {
+        type_name: "Patch",
+        key_field: "registry",
+        value_field: Some("dependencies"),
+        inline: false,
+    },
+];
+
+type FieldRef<'a> = (ID, &'a FieldType);
+
+// Looks up the keyed table for entries of the given object type, along with its key and value
+// fields.
+fn keyed_table(
+    object_type: &ObjectType,
+) -> Option<(&'static KeyedTable, FieldRef<'_>, Option<FieldRef<'_>>)> {
+    let keyed_table = KEYED_TABLES
+        .iter()
+        .find(|keyed_table| keyed_table.type_name == object_type.name)?;
+    let key_field = field_by_name(object_type, keyed_table.key_field)?;
+    let value_field = keyed_table
+        .value_field
+        .and_then(|name| field_by_name(object_type, name));
+    Some((keyed_table, key_field, value_field))
+}
+
+pub struct CargoImport {
+    pub root: D,
+    // Dotted paths of the keys that could not be mapped to a field, e.g. "package.description".
+    pub unmapped: Vec<String>,
+}
+
+impl Imported for CargoImport {
+    fn root(&self) -> &D {
+        &self.root
+    }
+
+    fn messages(&self) -> Vec<String> {
+        not_imported(&self.unmapped)
+    }
+}
+
+// Parses a Cargo.toml file into nodes, which are passed to `put`, and returns the digest of the
+// root `CargoManifest` node.
+pub fn import_cargo_toml<P: FnMut(Node) -> D>(
+    schema: &Schema,
+    source: &str,
+    put: &mut P,
+) -> anyhow::Result<CargoImport> {
+    let table: toml::Table = source.parse()?;
+    let object_type = schema
+        .object_types
+        .get(&CARGO_MANIFEST_TYPE_ID)
+        .ok_or_else(|| anyhow::anyhow!("schema does not define the CargoManifest type"))?;
+    let mut importer = Importer {
+        schema,
+        put,
+        unmapped: vec![],
+    };
+    let mut fields = BTreeMap::new();
+    importer.table_fields(&table, object_type, "", &mut fields);
+    let root = importer.object(CARGO_MANIFEST_TYPE_ID, fields);
+    Ok(CargoImport {
+        root,
+        unmapped: importer.unmapped,
+    })
+}
+
+fn field_by_name<'a>(object_type: &'a ObjectType, name: &str) -> Option<(ID, &'a FieldType)> {
+    object_type
+        .fields
+        .iter()
+        .find(|(_, field_type)| field_type.name == name)
+        .map(|(field_id, field_type)| (*field_id, field_type))
+}
+
+fn key_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+struct Importer<'a, P> {
+    schema: &'a Schema,
+    put: &'a mut P,
+    unmapped: Vec<String>,
+}
+
+impl<P: FnMut(Node) -> D> Importer<'_, P> {
+    fn node(&mut self, value: Value) -> D {
+        (self.put)(Node {
+            id: new_id(),
+            value,
+            schema: None,
+        })
+    }
+
+    fn object(&mut self, object_type_id: ID, fields: BTreeMap<ID, Vec<D>>) -> D {
+        self.node(Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        }))
+    }
+
+    // Adds the entries of the table to the fields of an object of the given type.
+    fn table_fields(
+        &mut self,
+        table: &toml::Table,
+        object_type: &ObjectType,
+        prefix: &str,
+        fields: &mut BTreeMap<ID, Vec<D>>,
+    ) {
+        for (key, value) in table {
+            let path = key_path(prefix, key);
+            match field_by_name(object_type, key) {
+                Some((field_id, field_type)) => {
+                    let digests = self.field(field_type, value, &path);
+                    fields.entry(field_id).or_default().extend(digests);
+                }
+                None => self.unmapped.push(path),
+            }
+        }
+    }
+
+    fn field(&mut self, field_type: &FieldType, value: &toml::Value, path: &str) -> Vec<D> {
+        match (&field_type.type_, value) {
+            (Type::Object(object_type_id), toml::Value::Table(table)) if field_type.repeated => {
+                self.keyed_entries(*object_type_id, table, path)
+            }
+            (_, toml::Value::Array(items)) if field_type.repeated => items
+                .iter()
+                .filter_map(|item| self.value(&field_type.type_, item, path))
+                .collect(),
+            _ => self
+                .value(&field_type.type_, value, path)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn value(&mut self, type_: &Type, value: &toml::Value, path: &str) -> Option<D> {
+        let schema = self.schema;
+        let value = match (type_, value) {
+            (Type::String, toml::Value::String(s)) => Value::String(s.clone()),
+            // E.g. `publish = false`, or a `metadata` table.
+            (Type::String, value) => Value::String(value.to_string()),
+            (Type::Int, toml::Value::Integer(i)) => Value::Int(*i),
+            (Type::Number, toml::Value::Float(f)) => Value::Number(*f),
+            (Type::Number, toml::Value::Integer(i)) => Value::Number(*i as f64),
+            (Type::Boolean, toml::Value::Boolean(b)) => Value::Boolean(*b),
+            (Type::Object(object_type_id), toml::Value::Table(table)) => {
+                let Some(object_type) = schema.object_types.get(object_type_id) else {
+                    self.unmapped.push(path.to_string());
+                    return None;
+                };
+                let mut fields = BTreeMap::new();
+                self.table_fields(table, object_type, path, &mut fields);
+                return Some(self.object(*object_type_id, fields));
+            }
+            _ => {
+                self.unmapped.push(path.to_string());
+                return None;
+            }
+        };
+        Some(self.node(value))
+    }
+
+    // Imports a table such as `[dependencies]` as one object per entry.
+    fn keyed_entries(&mut self, object_type_id: ID, table: &toml::Table, path: &str) -> Vec<D> {
+        let schema = self.schema;
+        let Some(object_type) = schema.object_types.get(&object_type_id) else {
+            self.unmapped.push(path.to_string());
+            return vec![];
+        };
+        let Some((_, key_field, value_field)) = keyed_table(object_type) else {
+            self.unmapped.push(path.to_string());
+            return vec![];
+        };
+        let mut digests = vec![];
+        for (key, value) in table {
+            let entry_path = key_path(path, key);
+            let mut fields = BTreeMap::new();
+            let key_digest = self.node(Value::String(key.clone()));
+            fields.insert(key_field.0, vec![key_digest]);
+            // Tables are the detailed form, unless the value field itself holds a table.
+            let detailed = match value_field {
+                Some((_, field_type)) => !matches!(field_type.type_, Type::Object(_)),
+                None => true,
+            };
+            match (value, value_field) {
+                (toml::Value::Table(table), _) if detailed => {
+                    self.table_fields(table, object_type, &entry_path, &mut fields);
+                }
+                (value, Some((field_id, field_type))) => {
+                    let value_digests = self.field(field_type, value, &entry_path);
+                    fields.insert(field_id, value_digests);
+                }
+                (_, None) => self.unmapped.push(entry_path),
+            }
+            digests.push(self.object(object_type_id, fields));
+        }
+        digests
+    }
+}
+
+// Order of the top-level sections in exported files; other fields follow, in the order of their ids.
+const SECTION_ORDER: &[&str] = &[
+    "package",
+    "dependencies",
+    "dev-dependencies",
+    "build-dependencies",
+    "target",
+    "features",
+    "patch",
+    "replace",
+    "profile",
+    "workspace",
+];
+
+// A `[header]` and the `key = value` lines under it. The root section has no header.
+struct Section {
+    path: Vec<String>,
+    entries: Vec<(String, toml::Value)>,
+}
+
+// Writes the `CargoManifest` document rooted at the given digest as a Cargo.toml file. The output
+// only depends on the document: sections are written in canonical order, and dependencies that
+// only set a version requirement are collapsed to `name = "version"`.
+pub fn export_cargo_toml<G: Fn(&D) -> Option<Node>>(
+    schema: &Schema,
+    get_node: &G,
+    root: &D,
+) -> anyhow::Result<String> {
+    let exporter = Exporter { schema, get_node };
+    let (object, object_type) = exporter.object(root)?;
+    if object.object_type_id != CARGO_MANIFEST_TYPE_ID {
+        anyhow::bail!("root is a {}, not a CargoManifest", object_type.name);
+    }
+    let mut sections = vec![];
+    exporter.sections(vec![], &object, object_type, None, &mut sections)?;
+    let mut out = String::new();
+    for section in sections
+        .iter()
+        .filter(|section| !section.entries.is_empty())
+    {
+        if !section.path.is_empty() {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let header: Vec<_> = section.path.iter().map(|key| toml_key(key)).collect();
+            out.push_str(&format!("[{}]\n", header.join(".")));
+        }
+        for (key, value) in &section.entries {
+            out.push_str(&format!("{} = {}\n", toml_key(key), value));
+        }
+    }
+    Ok(out)
+}
+
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if bare {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+// Fields of type `Type::String` in the schema that Cargo also accepts other values for. The
+// importer stores those values as TOML, e.g. `publish = false`, and they are written back as they
+// were; any other string field is written as a string, even if it looks like TOML.
+const RAW_TOML_FIELDS: &[&str] = &["publish", "metadata", "opt-level", "lto"];
+
+fn string_value(field_name: &str, s: String) -> toml::Value {
+    if !RAW_TOML_FIELDS.contains(&field_name) {
+        return toml::Value::String(s);
+    }
+    let raw = format!("value = {}", s)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .filter(|value| match value {
+            toml::Value::Boolean(_) | toml::Value::Array(_) | toml::Value::Table(_) => true,
+            // `opt-level` is either a number or "s" / "z".
+            toml::Value::Integer(_) => field_name == "opt-level",
+            _ => false,
+        });
+    raw.unwrap_or(toml::Value::String(s))
+}
+
+struct Exporter<'a, G> {
+    schema: &'a Schema,
+    get_node: &'a G,
+}
+
+impl<'a, G: Fn(&D) -> Option<Node>> Exporter<'a, G> {
+    fn node(&self, digest: &D) -> anyhow::Result<Node> {
+        (self.get_node)(digest).ok_or_else(|| anyhow::anyhow!("node {} not found", digest.to_hex()))
+    }
+
+    fn object(&self, digest: &D) -> anyhow::Result<(ObjectValue, &'a ObjectType)> {
+        let Value::Object(object) = self.node(digest)?.value else {
+            anyhow::bail!("node {} is not an object", digest.to_hex());
+        };
+        let object_type = self
+            .schema
+            .object_types
+            .get(&object.object_type_id)
+            .ok_or_else(|| anyhow::anyhow!("unknown object type {}", object.object_type_id))?;
+        Ok((object, object_type))
+    }
+
+    // Non-empty fields of the object, in the order in which they are written.
+    fn fields<'o>(
+        &self,
+        object: &'o ObjectValue,
+        object_type: &'a ObjectType,
+        order: &[&str],
+    ) -> anyhow::Result<Vec<(ID, &'a FieldType, &'o [D])>> {
+        let mut fields = vec![];
+        for (field_id, digests) in object.fields.iter().filter(|(_, d)| !d.is_empty()) {
+            let field_type = object_type.fields.get(field_id).ok_or_else(|| {
+                anyhow::anyhow!("field {} is not defined in {}", field_id, object_type.name)
+            })?;
+            fields.push((*field_id, field_type, digests.as_slice()));
+        }
+        fields.sort_by_key(|(_, field_type, _)| {
+            order
+                .iter()
+                .position(|name| *name == field_type.name)
+                .unwrap_or(order.len())
+        });
+        Ok(fields)
+    }
+
+    // Appends the section of the object at `path`, followed by those of nested tables. `skip` is
+    // the key field of a keyed table entry, which is part of the header rather than the section.
+    fn sections(
+        &self,
+        path: Vec<String>,
+        object: &ObjectValue,
+        object_type: &'a ObjectType,
+        skip: Option<ID>,
+        sections: &mut Vec<Section>,
+    ) -> anyhow::Result<()> {
+        let order = if path.is_empty() { SECTION_ORDER } else { &[] };
+        let mut section = Section {
+            path: path.clone(),
+            entries: vec![],
+        };
+        let mut nested = vec![];
+        for (field_id, field_type, digests) in self.fields(object, object_type, order)? {
+            if Some(field_id) == skip {
+                continue;
+            }
+            let mut field_path = path.clone();
+            field_path.push(field_type.name.clone());
+            let Type::Object(child_type_id) = field_type.type_ else {
+                section.entries.push((
+                    field_type.name.clone(),
+                    self.field_value(field_type, digests)?,
+                ));
+                continue;
+            };
+            let keyed = self
+                .schema
+                .object_types
+                .get(&child_type_id)
+                .and_then(keyed_table);
+            match keyed {
+                Some((keyed_table, _, _)) if field_type.repeated && keyed_table.inline => {
+                    nested.push(Section {
+                        path: field_path,
+                        entries: self.keyed_entries(digests)?,
+                    });
+                }
+                Some((_, (key_id, _), value_field)) if field_type.repeated => {
+                    for digest in digests {
+                        let (entry, entry_type) = self.object(digest)?;
+                        let mut entry_path = field_path.clone();
+                        entry_path.push(self.key(&entry, key_id)?);
+                        match value_field {
+                            // E.g. `[patch.crates-io]`, whose entries are dependencies.
+                            Some((value_id, _)) => nested.push(Section {
+                                path: entry_path,
+                                entries: self.keyed_entries(
+                                    entry.fields.get(&value_id).map_or(&[], |d| d),
+                                )?,
+                            }),
+                            None => self.sections(
+                                entry_path,
+                                &entry,
+                                entry_type,
+                                Some(key_id),
+                                &mut nested,
+                            )?,
+                        }
+                    }
+                }
+                _ if !field_type.repeated => {
+                    let (child, child_type) = self.object(&digests[0])?;
+                    self.sections(field_path, &child, child_type, None, &mut nested)?;
+                }
+                _ => section.entries.push((
+                    field_type.name.clone(),
+                    self.field_value(field_type, digests)?,
+                )),
+            }
+        }
+        sections.push(section);
+        sections.extend(nested);
+        Ok(())
+    }
+
+    fn key(&self, object: &ObjectValue, key_id: ID) -> anyhow::Result<String> {
+        let digest = object
+            .fields
+            .get(&key_id)
+            .and_then(|digests| digests.first())
+            .ok_or_else(|| anyhow::anyhow!("table entry without a key"))?;
+        match self.node(digest)?.value {
+            Value::String(key) => Ok(key),
+            _ => anyhow::bail!("table entry with a key that is not a string"),
+        }
+    }
+
+    // Entries of a keyed table as `key = value` pairs, where the value is the value field alone if
+    // no other field is set, and an inline table otherwise.
+    fn keyed_entries(&self, digests: &[D]) -> anyhow::Result<Vec<(String, toml::Value)>> {
+        digests
+            .iter()
+            .map(|digest| {
+                let (entry, entry_type) = self.object(digest)?;
+                let (_, (key_id, _), value_field) = keyed_table(entry_type)
+                    .ok_or_else(|| anyhow::anyhow!("{} is not a table entry", entry_type.name))?;
+                let key = self.key(&entry, key_id)?;
+                let set: Vec<ID> = entry
+                    .fields
+                    .iter()
+                    .filter(|(field_id, digests)| **field_id != key_id && !digests.is_empty())
+                    .map(|(field_id, _)| *field_id)
+                    .collect();
+                let value = match value_field {
+                    Some((value_id, value_type)) if set == [value_id] => {
+                        self.field_value(value_type, &entry.fields[&value_id])?
+                    }
+                    _ => toml::Value::Table(self.inline_table(&entry, entry_type, Some(key_id))?),
+                };
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    fn inline_table(
+        &self,
+        object: &ObjectValue,
+        object_type: &'a ObjectType,
+        skip: Option<ID>,
+    ) -> anyhow::Result<toml::Table> {
+        let mut table = toml::Table::new();
+        for (field_id, field_type, digests) in self.fields(object, object_type, &[])? {
+            if Some(field_id) == skip {
+                continue;
+            }
+            let keyed = match field_type.type_ {
+                Type::Object(child_type_id) if field_type.repeated => self
+                    .schema
+                    .object_types
+                    .get(&child_type_id)
+                    .and_then(keyed_table)
+                    .is_some(),
+                _ => false,
+            };
+            let value = if keyed {
+                toml::Value::Table(self.keyed_entries(digests)?.into_iter().collect())
+            } else {
+                self.field_value(field_type, digests)?
+            };
+            table.insert(field_type.name.clone(), value);
+        }
+        Ok(table)
+    }
+
+    // Only the first entry of non-repeated fields is written; validation reports any others.
+    fn field_value(&self, field_type: &FieldType, digests: &[D]) -> anyhow::Result<toml::Value> {
+        if field_type.repeated {
+            let values = digests
+                .iter()
+                .map(|digest| self.value(field_type, digest))
+                .collect::<anyhow::Result<_>>()?;
+            Ok(toml::Value::Array(values))
+        } else {
+            self.value(field_type, &digests[0])
+        }
+    }
+
+    fn value(&self, field_type: &FieldType, digest: &D) -> anyhow::Result<toml::Value> {
+        Ok(match self.node(digest)?.value {
+            Value::String(s) => string_value(&field_type.name, s),
+            Value::Int(i) => toml::Value::Integer(i),
+            Value::Number(n) => toml::Value::Float(n),
+            Value::Boolean(b) => toml::Value::Boolean(b),
+            Value::Object(_) => {
+                let (object, object_type) = self.object(digest)?;
+                toml::Value::Table(self.inline_table(&object, object_type, None)?)
+            }
+            Value::Empty => anyhow::bail!("field {} has no value", field_type.name),
+        })
+    }
+}
+
+#[cfg(test)]
+use crate::{create_schema, HasDigest, MemoryStore, Store};
+
+#[test]
+fn test_import_cargo_toml() {
+    let source = r#"
+[package]
+name = "demo"
+version = "0.1.0"
+edition = "2021"
+publish = false
+description = "not in the schema"
+
+[dependencies]
+serde = "1.0"
+leptos = { version = "0.6", features = ["csr"] }
+
+[dependencies.local]
+path = "../local"
+unknown = 1
+
+[features]
+default = ["serde"]
+
+[profile.release]
+lto = "fat"
+codegen-units = 1
+
+[patch.crates-io]
+serde = { git = "https://github.com/serde-rs/serde" }
+"#;
+    let schema = create_schema();
+    let store = MemoryStore::new();
+    let import = import_cargo_toml(&schema, source, &mut |node: Node| store.put(node)).unwrap();
+    assert_eq!(
+        import.unmapped,
+        vec!["dependencies.local.unknown", "package.description"]
+    );
+
+    let value = |digest: &D| store.get(digest).unwrap().value;
+    // Entries of the field with the given name, of the object with the given digest.
+    let field = |digest: &D, name: &str| -> Vec<D> {
+        let Value::Object(object) = value(digest) else {
+            panic!("expected object value");
+        };
+        let (field_id, _) =
+            field_by_name(&schema.object_types[&object.object_type_id], name).unwrap();
+        object.fields.get(&field_id).cloned().unwrap_or_default()
+    };
+    let values = |digest: &D, name: &str| field(digest, name).iter().map(value).collect::<Vec<_>>();
+    let string = |s: &str| Value::String(s.to_string());
+
+    let package = &field(&import.root, "package")[0];
+    assert_eq!(values(package, "name"), vec![string("demo")]);
+    assert_eq!(values(package, "publish"), vec![string("false")]);
+
+    let dependencies = field(&import.root, "dependencies");
+    let names: Vec<_> = dependencies
+        .iter()
+        .flat_map(|d| values(d, "name"))
+        .collect();
+    assert_eq!(
+        names,
+        vec![string("leptos"), string("local"), string("serde")]
+    );
+    assert_eq!(values(&dependencies[0], "version"), vec![string("0.6")]);
+    assert_eq!(values(&dependencies[0], "features"), vec![string("csr")]);
+    assert_eq!(values(&dependencies[1], "path"), vec![string("../local")]);
+    assert_eq!(values(&dependencies[2], "version"), vec![string("1.0")]);
+
+    let feature = &field(&import.root, "features")[0];
+    assert_eq!(values(feature, "name"), vec![string("default")]);
+    assert_eq!(values(feature, "enables"), vec![string("serde")]);
+
+    let profile = &field(&import.root, "profile")[0];
+    assert_eq!(values(profile, "name"), vec![string("release")]);
+    assert_eq!(values(profile, "codegen-units"), vec![Value::Int(1)]);
+
+    let patch = &field(&import.root, "patch")[0];
+    assert_eq!(values(patch, "registry"), vec![string("crates-io")]);
+    let patched = &field(patch, "dependencies")[0];
+    assert_eq!(values(patched, "name"), vec![string("serde")]);
+    assert_eq!(
+        values(patched, "git"),
+        vec![string("https://github.com/serde-rs/serde")]
+    );
+}
+
+#[test]
+fn test_import_cargo_toml_invalid() {
+    let mut put = |node: Node| node.digest();
+    assert!(import_cargo_toml(&create_schema(), "[package", &mut put).is_err());
+}
+
+#[cfg(test)]
+const TEST_EXPORT_SOURCE: &str = r#"
+[workspace]
+members = ["a", "b"]
+
+[package]
+name = "demo"
+version = "0.1.0"
+edition = "2021"
+publish = false
+
+[dependencies]
+serde = "1.0"
+leptos = { version = "0.6", features = ["csr"] }
+
+[dependencies.local]
+path = "../local"
+
+[dev-dependencies]
+serde = { version = "1.0", default-features = false }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[features]
+default = ["serde"]
+
+[profile.release]
+lto = "fat"
+codegen-units = 1
+opt-level = 3
+
+[patch.crates-io]
+serde = { git = "https://github.com/serde-rs/serde" }
+"#;
+
+#[test]
+fn test_export_cargo_toml() {
+    let schema = create_schema();
+    let store = MemoryStore::new();
+    let mut put = |node: Node| store.put(node);
+    let import = import_cargo_toml(&schema, TEST_EXPORT_SOURCE, &mut put).unwrap();
+    assert!(import.unmapped.is_empty());
+    let get_node = |digest: &D| store.get(digest);
+    let exported = export_cargo_toml(&schema, &get_node, &import.root).unwrap();
+    assert_eq!(
+        exported,
+        r#"[package]
+name = "demo"
+version = "0.1.0"
+publish = false
+edition = "2021"
+
+[dependencies]
+leptos = { features = ["csr"], version = "0.6" }
+local = { path = "../local" }
+serde = "1.0"
+
+[dev-dependencies]
+serde = { default-features = false, version = "1.0" }
+
+[target."cfg(unix)".dependencies]
+libc = "0.2"
+
+[features]
+default = ["serde"]
+
+[patch.crates-io]
+serde = { git = "https://github.com/serde-rs/serde" }
+
+[profile.release]
+opt-level = 3
+lto = "fat"
+codegen-units = 1
+
+[workspace]
+members = ["a", "b"]
+"#
+    );
+}
+
+#[test]
+fn test_export_cargo_toml_round_trip() {
+    let schema = create_schema();
+    let store = MemoryStore::new();
+    let round_trip = |source: &str| {
+        let mut put = |node: Node| store.put(node);
+        let root = import_cargo_toml(&schema, source, &mut put).unwrap().root;
+        export_cargo_toml(&schema, &|digest: &D| store.get(digest), &root).unwrap()
+    };
+    let exported = round_trip(TEST_EXPORT_SOURCE);
+    // Same manifest, as far as a TOML parser is concerned.
+    assert_eq!(
+        exported.parse::<toml::Table>().unwrap(),
+        TEST_EXPORT_SOURCE.parse::<toml::Table>().unwrap()
+    );
+    assert_eq!(round_trip(&exported), exported);
+    // Strings that look like other TOML values stay strings.
+    let source = "[package]\nname = \"true\"\nversion = \"[1]\"\n";
+    assert_eq!(round_trip(source), source);
+}
+
+#[test]
+fn test_export_cargo_toml_not_a_manifest() {
+    let node = Node {
+        id: 0,
+        value: Value::String("demo".to_string()),
+        schema: None,
+    };
+    let digest = node.digest();
+    let get_node = |d: &D| (*d == digest).then(|| node.clone());
+    assert!(export_cargo_toml(&create_schema(), &get_node, &digest).is_err());
+}
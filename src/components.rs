@@ -8,37 +8,38 @@ use super::Path;
 use super::Position;
 use super::ID;
 use super::D;
-use super::FieldType;
 use super::ObjectValue;
 use super::format_path;
-use super::get_item;
 use super::storage;
 use super::*;
 
 #[component]
 pub fn App() -> impl IntoView {
     logging::log!("rendering App");
-    let (schema, _set_schema) = create_signal(create_schema());
+    let store = use_node_store();
+    let get_node = move |digest: &D| store.get(digest);
+    let (schema, set_schema) = create_signal(create_schema());
+    // New documents are authored against the built-in schema.
+    let default_schema_digest = store_schema(&store, &schema.get_untracked());
 
     let (api_key, set_api_key) = create_signal("api-key".to_string());
 
     let (fetch_queue, set_fetch_queue) = create_signal(Vec::<D>::new());
 
-    let value = create_rw_signal(create_value());
     let _root_type = Type::Object(schema.get_untracked().root_object_type_id);
 
     let (debug, _set_debug) = create_signal(false);
 
 
-    let (history, _set_history) = create_signal(Vec::<D>::new());
-
-    let _store = Arc::new(LocalStorage::<Node, D>::new());
+    let history = create_rw_signal(History::default());
+    let refs = create_rw_signal(load_refs());
 
     let node = Node {
         id: 1,
         value: create_value(),
+        schema: Some(default_schema_digest.clone()),
     };
-    let d = set_item(&node);
+    let d = store.put(node);
 
 
     // TODO: derived signals have different types.
@@ -51,11 +52,41 @@ pub fn App() -> impl IntoView {
     let focus_path = create_rw_signal(Path::default());
     let focused_digest = create_memo(move |_| {
         let path = focus_path.get();
-        let digest = find_value(&root_digest_memo.get(), &path).unwrap();
+        let digest = find_value(&store, &root_digest_memo.get(), &path).unwrap();
         logging::log!("focused_digest {:?}", digest.to_hex());
         digest
     });
     let focus_path_memo = create_memo(move |_| focus_path.get().clone());
+
+    // Like `get_node`, but missing nodes are read again once they are stored, e.g. while a
+    // document opened from the URL hash is being downloaded.
+    let get_node_tracked = move |digest: &D| store.get_signal(digest).get();
+    // Ways in which the document does not conform to its schema, by path.
+    let violations = create_memo(move |_| {
+        let root = root_digest.get();
+        schema.with(|schema| validate_document(schema, &get_node_tracked, &root))
+    });
+    // Lint findings, recomputed for every new root and when missing nodes are stored.
+    let lint_registry = store_value(default_lint_registry());
+    let diagnostics = create_memo(move |_| {
+        let root = root_digest.get();
+        schema.with(|schema| {
+            lint_registry.with_value(|registry| registry.run(schema, &get_node_tracked, &root))
+        })
+    });
+    let errors = create_memo(move |_| {
+        let mut errors = HashMap::<Path, Vec<String>>::new();
+        for (path, violation) in violations.get() {
+            errors.entry(path).or_default().push(violation.to_string());
+        }
+        for diagnostic in diagnostics.get() {
+            errors
+                .entry(diagnostic.path.clone())
+                .or_default()
+                .push(diagnostic.to_string());
+        }
+        errors
+    });
     let _selected_element = create_memo(move |_| format_path(&selected_path.get()));
 
     let (response, set_response) = create_signal("---".to_string());
@@ -69,21 +100,72 @@ pub fn App() -> impl IntoView {
         logging::log!("initializing App");
         // First try to read from the hash.
         let hash = window().location().hash().unwrap();
-        if hash.len() > 1 {
+        let d = if hash.len() > 1 {
             let d = D::from_hex(&hash[1..]);
             logging::log!("obtained root digest from URL hash fragment: {:?}", d.to_hex());
         set_root_digest(d.clone());
+            d
         } else {
             // If the hash is empty, create a new root node.
             let node = Node {
                 id: new_id(),
                 value: create_value(),
+                schema: Some(default_schema_digest.clone()),
             };
-            let d = set_item(&node);
+            let d = store.put(node);
             logging::log!("no root digest, creating empty root node: {:?}", d.to_hex());
         set_root_digest(d.clone());
             set_root_digest_in_url_hash(&d);
+            d
+        };
+        // Resume the history of the previous session, on top of the root being displayed.
+        let mut restored_history = load_history();
+        restored_history.push(d);
+        history.set(restored_history);
+    });
+
+    // Display the document with the schema it was authored against, if it references one.
+    let root_schema_digest = create_memo(move |_| {
+        store
+            .get_signal(&root_digest.get())
+            .get()
+            .and_then(|node| node.schema)
+    });
+    create_effect(move |_| {
+        let Some(schema_digest) = root_schema_digest.get() else {
+            return;
+        };
+        if let Some(root_schema) = load_schema(&store, &schema_digest) {
+            if root_schema != schema.get_untracked() {
+                set_schema(root_schema);
+            }
+            return;
         }
+        spawn_local(async move {
+            match download_missing(&store, &fetch_node, schema_digest.clone()).await {
+                Ok(()) => {
+                    if let Some(root_schema) = load_schema(&store, &schema_digest) {
+                        set_schema(root_schema);
+                    }
+                }
+                Err(err) => {
+                    logging::error!(
+                        "failed to download schema {}: {}",
+                        schema_digest.to_hex(),
+                        err
+                    );
+                    set_response(format!("failed to download schema: {}", err));
+                }
+            }
+        });
+    });
+
+    create_effect(move |_| {
+        history.with(|history| {
+            if !history.is_empty() {
+                save_history(history);
+            }
+        })
     });
 
     /* 
@@ -112,7 +194,8 @@ pub fn App() -> impl IntoView {
         match action {
             Action::Noop => {}
             Action::Update(path, value) => {
-                let new_d = update_node(&root_digest(), &path, value);
+                let new_d = update_node(&store, &root_digest(), &path, value);
+                history.update(|history| history.push_update(&path, new_d.clone()));
                 // set_root(&new_d);
                 set_root_digest(new_d.clone());
                 set_root_digest_in_url_hash(&new_d);
@@ -123,39 +206,68 @@ pub fn App() -> impl IntoView {
                 position,
                 value,
             } => {
-                // TODO: handle position
-                match position {
-                    Position::Last => {}
-                    _ => {}
-                };
-                let new_d = update_node_value(&root_digest(), &path, |v| match v {
+                let new_d = update_node_value(&store, &root_digest(), &path, |v| match v {
                     Value::Object(mut object) => {
-                        object.append(
+                        object.insert(
                             field_id,
-                            set_item(&Node {
+                            position,
+                            store.put(Node {
                                 id: new_id(),
                                 value,
+                                schema: None,
                             }),
                         );
                         Value::Object(object)
                     }
                     _ => panic!("expected object value"),
                 });
+                history.update(|history| history.push(new_d.clone()));
                 set_root(&new_d);
                 set_root_digest(new_d);
             }
             Action::Delete { path } => {
                 let (parent_path, selector) = path.split_at(path.len() - 1);
                 let selector = selector.first().unwrap();
-                let new_d = update_node_value(&root_digest(), &parent_path.to_vec(), |v| match v {
-                    Value::Object(mut object) => {
-                        object.delete(selector.field_id, selector.index);
-                        Value::Object(object)
-                    }
-                    _ => panic!("expected object value"),
-                });
+                let new_d =
+                    update_node_value(&store, &root_digest(), &parent_path.to_vec(), |v| match v {
+                        Value::Object(mut object) => {
+                            object.delete(selector.field_id, selector.index);
+                            Value::Object(object)
+                        }
+                        _ => panic!("expected object value"),
+                    });
+                history.update(|history| history.push(new_d.clone()));
+                set_root(&new_d);
+                set_root_digest(new_d);
+            }
+            Action::Move { path, position } => {
+                // The root cannot be moved.
+                let Some((selector, parent_path)) = path.split_last() else {
+                    return;
+                };
+                let mut new_index = None;
+                let new_d =
+                    update_node_value(&store, &root_digest(), &parent_path.to_vec(), |v| match v {
+                        Value::Object(mut object) => {
+                            new_index = object.move_to(selector.field_id, selector.index, position);
+                            Value::Object(object)
+                        }
+                        _ => panic!("expected object value"),
+                    });
+                history.update(|history| history.push(new_d.clone()));
                 set_root(&new_d);
                 set_root_digest(new_d);
+                // Keep the moved entry selected.
+                if let Some(index) = new_index {
+                    if selected_path.get_untracked() == path {
+                        let mut new_path = parent_path.to_vec();
+                        new_path.push(Selector {
+                            field_id: selector.field_id,
+                            index,
+                        });
+                        selected_path.set(new_path);
+                    }
+                }
             }
             Action::SetFocus { path } => {
                 focus_path.set(path);
@@ -163,6 +275,216 @@ pub fn App() -> impl IntoView {
         }
     };
 
+    // Display a root from the history.
+    let restore = move |d: D| {
+        // The focused path may not exist in the restored tree.
+        if find_value(&store, &d, &focus_path.get_untracked()).is_none() {
+            focus_path.set(Path::default());
+        }
+        set_root_digest(d.clone());
+        set_root_digest_in_url_hash(&d);
+    };
+    let undo = move || {
+        if let Some(d) = history.try_update(History::undo).flatten() {
+            restore(d);
+        }
+    };
+    let redo = move || {
+        if let Some(d) = history.try_update(History::redo).flatten() {
+            restore(d);
+        }
+    };
+    // Display the root of a ref, keeping the current one in the history.
+    let switch_root = move |d: D| {
+        history.update(|history| history.push(d.clone()));
+        restore(d);
+    };
+
+    let on_schema_action = move |action: SchemaAction| {
+        logging::log!("schema action {:?}", action);
+        let mut new_schema = schema.get_untracked();
+        new_schema.apply(action);
+        let schema_digest = store_schema(&store, &new_schema);
+        set_schema(new_schema);
+        // Point the document to the new version of the schema.
+        let new_d = update_node_schema(&store, &root_digest.get_untracked(), schema_digest);
+        history.update(|history| history.push(new_d.clone()));
+        set_root_digest(new_d.clone());
+        set_root_digest_in_url_hash(&new_d);
+    };
+    let (show_schema_editor, set_show_schema_editor) = create_signal(false);
+
+    // Replace the document with one imported from another format.
+    let (import_messages, set_import_messages) = create_signal(Vec::<String>::new());
+    let on_import = move |(format, source, object_type_id): (Format, String, ID)| {
+        // JSON may be in any type of the current schema; other formats map to the built-in types.
+        let import_schema = match format {
+            Format::Json => schema.get_untracked(),
+            _ => create_schema(),
+        };
+        let article_type_id = match format {
+            Format::FlatArticleMarkdown => FLAT_ARTICLE_TYPE_ID,
+            _ => TREE_ARTICLE_TYPE_ID,
+        };
+        // The document is validated against the schema it points to, so it has to be rooted at
+        // the imported type.
+        let import_schema = Schema {
+            root_object_type_id: match format {
+                Format::CargoToml => CARGO_MANIFEST_TYPE_ID,
+                Format::Dockerfile => DOCKERFILE_TYPE_ID,
+                Format::FlatArticleMarkdown | Format::TreeArticleMarkdown => article_type_id,
+                Format::Json => object_type_id,
+                Format::Bundle => import_schema.root_object_type_id,
+            },
+            ..import_schema
+        };
+        let mut put = |node: Node| store.put(node);
+        let result = match format {
+            Format::CargoToml => {
+                import_cargo_toml(&import_schema, &source, &mut put).map(import_summary)
+            }
+            Format::Dockerfile => {
+                import_dockerfile(&import_schema, &source, &mut put).map(import_summary)
+            }
+            Format::FlatArticleMarkdown | Format::TreeArticleMarkdown => {
+                import_markdown(&source, article_type_id, &mut put).map(import_summary)
+            }
+            Format::Json => serde_json::from_str(&source)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| import_json(&import_schema, object_type_id, &json, &mut put))
+                .map(import_summary),
+            Format::Bundle => import_bundle(&source, &mut put).map(import_summary),
+        };
+        match result {
+            Ok((root, messages)) => {
+                // Bundles carry the schema they were authored against.
+                let new_d = match format {
+                    Format::Bundle => root,
+                    _ => update_node_schema(&store, &root, store_schema(&store, &import_schema)),
+                };
+                history.update(|history| history.push(new_d.clone()));
+                focus_path.set(Path::default());
+                selected_path.set(Path::default());
+                restore(new_d);
+                set_import_messages(messages);
+            }
+            Err(err) => set_import_messages(vec![format!("import failed: {}", err)]),
+        }
+    };
+    let (show_import, set_show_import) = create_signal(false);
+    let (show_export, set_show_export) = create_signal(false);
+    let (show_storage, set_show_storage) = create_signal(false);
+    let (show_refs, set_show_refs) = create_signal(false);
+    let (show_merge, set_show_merge) = create_signal(false);
+
+    let mode = create_rw_signal(Mode::Normal);
+    let keymap = store_value(load_keymap());
+
+    let on_command = move |command: Command| {
+        logging::log!("command {:?}", command);
+        let root = root_digest.get_untracked();
+        let path = selected_path.get_untracked();
+        match command {
+            Command::SelectParent => selected_path.set(parent(&path)),
+            Command::SelectChild => {
+                selected_path.set(child(&get_node, &root, &path));
+            }
+            Command::SelectPrev => {
+                selected_path.set(prev(&get_node, &root, &path));
+            }
+            Command::SelectNext => {
+                selected_path.set(next(&get_node, &root, &path));
+            }
+            Command::StartEditing => {
+                let is_primitive = find_value(&store, &root, &path)
+                    .and_then(|digest| store.get(&digest))
+                    .map(|node| !matches!(node.value, Value::Object(_)))
+                    .unwrap_or(false);
+                if is_primitive {
+                    mode.set(Mode::Edit);
+                }
+            }
+            Command::StopEditing => mode.set(Mode::Normal),
+            Command::Append | Command::InsertBefore => {
+                let Some(selector) = path.last() else {
+                    return;
+                };
+                let parent_path = parent(&path);
+                let field_type = find_value(&store, &root, &parent_path)
+                    .and_then(|digest| store.get(&digest))
+                    .and_then(|node| match node.value {
+                        Value::Object(object) => schema
+                            .get_untracked()
+                            .object_types
+                            .get(&object.object_type_id)
+                            .and_then(|object_type| object_type.fields.get(&selector.field_id))
+                            .cloned(),
+                        _ => None,
+                    });
+                let (position, new_index) = if command == Command::Append {
+                    (Position::After(selector.index), selector.index + 1)
+                } else {
+                    (Position::Before(selector.index), selector.index)
+                };
+                match field_type {
+                    Some(field_type) if field_type.repeated => {
+                        on_action(Action::Append {
+                            path: parent_path.clone(),
+                            field_id: selector.field_id,
+                            position,
+                            value: field_type.type_.default_value(),
+                        });
+                        let mut new_path = parent_path;
+                        new_path.push(Selector {
+                            field_id: selector.field_id,
+                            index: new_index,
+                        });
+                        selected_path.set(new_path);
+                    }
+                    _ => {}
+                }
+            }
+            Command::MoveUp => {
+                if let Some(selector) = path.last() {
+                    if selector.index > 0 {
+                        let position = Position::Before(selector.index - 1);
+                        on_action(Action::Move { path, position });
+                    }
+                }
+            }
+            Command::MoveDown => {
+                if let Some(selector) = path.last() {
+                    let position = Position::After(selector.index + 1);
+                    on_action(Action::Move { path, position });
+                }
+            }
+            Command::Delete => {
+                if !path.is_empty() {
+                    on_action(Action::Delete { path: path.clone() });
+                    selected_path.set(parent(&path));
+                }
+            }
+            Command::Focus => on_action(Action::SetFocus { path }),
+            Command::Undo => undo(),
+            Command::Redo => redo(),
+        }
+    };
+
+    let _ = window_event_listener(ev::keydown, move |ev| {
+        let mode = mode.get_untracked();
+        // Keys typed into inputs that are not part of the document (e.g. the API key) are not
+        // commands.
+        let tag_name = event_target::<web_sys::HtmlElement>(&ev).tag_name();
+        if mode == Mode::Normal && (tag_name == "INPUT" || tag_name == "TEXTAREA") {
+            return;
+        }
+        let key = Key::from_event(&ev);
+        if let Some(command) = keymap.with_value(|keymap| keymap.command(mode, key)) {
+            ev.prevent_default();
+            on_command(command);
+        }
+    });
+
     let queue_fetch = move |digest: D| {
         if digest.is_empty() {
             logging::log!("empty digest; ignoring");
@@ -173,7 +495,10 @@ pub fn App() -> impl IntoView {
         //     queue.push(digest);
         // });
         spawn_local_with_current_owner(async move {
-            download(digest).await.unwrap();
+            if let Err(err) = download(&store, &fetch_node, digest).await {
+                logging::error!("{}", err);
+                set_response(err.to_string());
+            }
         }).unwrap();
     };
 
@@ -181,23 +506,94 @@ pub fn App() -> impl IntoView {
         <div class="">
             // <List/>
             <div>sel: {move || format_path(&selected_path.get())}</div>
+            <div>mode: {move || format!("{:?}", mode.get())}</div>
             <div>root_digest: <Digest d=root_digest_memo /></div>
             <div>focused: {move || format_path(&focus_path.get())}</div>
-            <div>hist: {move || format!("{:?}", history.get())}</div>
+            <div>violations: {move || violations.with(Vec::len)}</div>
+            <div>
+                hist:
+                {move || history.with(|history| format!("{}/{}", history.position() + 1, history.len()))}
+            </div>
+            <button
+                class="button"
+                on:click=move |_| set_show_schema_editor.update(|show| *show = !*show)
+            >
+
+                Schema
+            </button>
+            <button class="button" on:click=move |_| set_show_import.update(|show| *show = !*show)>
+                Import
+            </button>
+            <button class="button" on:click=move |_| set_show_export.update(|show| *show = !*show)>
+                Export
+            </button>
+            <button class="button" on:click=move |_| set_show_refs.update(|show| *show = !*show)>
+                Refs
+            </button>
+            <button class="button" on:click=move |_| set_show_merge.update(|show| *show = !*show)>
+                Merge
+            </button>
+            <button class="button" on:click=move |_| set_show_storage.update(|show| *show = !*show)>
+                Storage
+            </button>
+            <Show when=move || show_schema_editor.get()>
+                <SchemaEditor schema=schema on_schema_action=on_schema_action/>
+            </Show>
+            <Show when=move || show_import.get()>
+                <ImportPanel schema=schema on_import=on_import messages=import_messages/>
+            </Show>
+            <Show when=move || show_export.get()>
+                <ExportPanel schema=schema root=root_digest_memo/>
+            </Show>
+            <Show when=move || show_storage.get()>
+                <StoragePanel history=history refs=refs root=root_digest_memo/>
+            </Show>
+            <Show when=move || show_refs.get()>
+                <RefsPanel refs=refs root=root_digest_memo on_switch=switch_root/>
+            </Show>
+            <Show when=move || show_merge.get()>
+                <MergePanel refs=refs root=root_digest_memo on_merge=switch_root/>
+            </Show>
             <ObjectView
                 schema=schema
                 digest=focused_digest
                 // TODO: Does not propagate correctly.
                 path=focus_path_memo
                 selected=selected_path
+                mode=mode
+                errors=errors
                 on_action=on_action
                 debug=debug
                 queue_fetch=queue_fetch
             />
+            <DiagnosticsPanel
+                diagnostics=diagnostics
+                on_jump=move |path: Path| {
+                    selected_path.set(path.clone());
+                    on_action(Action::SetFocus { path });
+                }
+                on_action=on_action
+            />
+            <button
+                class="button"
+                disabled=move || !history.with(History::can_undo)
+                on:click=move |_| undo()
+            >
+
+                Undo
+            </button>
+            <button
+                class="button"
+                disabled=move || !history.with(History::can_redo)
+                on:click=move |_| redo()
+            >
+
+                Redo
+            </button>
             <button
                 class="button"
                 on:click=move |_| {
-                    selected_path.set(parent(&schema.get(), &value.get(), &selected_path.get()));
+                    selected_path.set(parent(&selected_path.get()));
                 }
             >
 
@@ -206,7 +602,7 @@ pub fn App() -> impl IntoView {
             <button
                 class="button"
                 on:click=move |_| {
-                    selected_path.set(child(&schema.get(), &value.get(), &selected_path.get()));
+                    selected_path.set(child(&get_node, &root_digest.get(), &selected_path.get()));
                 }
             >
 
@@ -215,7 +611,7 @@ pub fn App() -> impl IntoView {
             <button
                 class="button"
                 on:click=move |_| {
-                    selected_path.set(prev(&schema.get(), &value.get(), &selected_path.get()));
+                    selected_path.set(prev(&get_node, &root_digest.get(), &selected_path.get()));
                 }
             >
 
@@ -225,7 +621,7 @@ pub fn App() -> impl IntoView {
             <button
                 class="button"
                 on:click=move |_| {
-                    selected_path.set(next(&schema.get(), &value.get(), &selected_path.get()));
+                    selected_path.set(next(&get_node, &root_digest.get(), &selected_path.get()));
                 }
             >
 
@@ -236,7 +632,7 @@ pub fn App() -> impl IntoView {
                 class="button"
                 on:click=move |_| {
                     spawn_local_with_current_owner(async move {
-                            upload(api_key.get(), root_digest.get()).await.unwrap();
+                            upload(&store, api_key.get(), root_digest.get()).await.unwrap();
                         })
                         .unwrap();
                 }
@@ -264,6 +660,58 @@ pub fn App() -> impl IntoView {
     }
 }
 
+#[component]
+fn DiagnosticsPanel(
+    diagnostics: Memo<Vec<Diagnostic>>,
+    // Called with the path of a diagnostic to bring it into view.
+    #[prop(into)] on_jump: Callback<Path>,
+    #[prop(into)] on_action: Callback<Action>,
+) -> impl IntoView {
+    let severity_class = |severity: Severity| match severity {
+        Severity::Error => "text-red",
+        Severity::Warning => "text-yellow",
+        Severity::Info => "text-blue",
+    };
+    view! {
+        <div class="border rounded p-1">
+            <div>diagnostics: {move || diagnostics.with(Vec::len)}</div>
+            <For
+                each=move || diagnostics.get().into_iter().enumerate()
+                key=|(index, diagnostic)| (*index, diagnostic.to_string(), format_path(&diagnostic.path))
+                children=move |(_, diagnostic)| {
+                    let path = diagnostic.path.clone();
+                    let location = if path.is_empty() {
+                        "(root)".to_string()
+                    } else {
+                        format_path(&path)
+                    };
+                    let fix = diagnostic.fix.clone().map(|Fix { description, action }| {
+                        view! {
+                            <button class="button" on:click=move |_| on_action(action.clone())>
+                                {description}
+                            </button>
+                        }
+                    });
+                    view! {
+                        <div class="flex gap-2 items-center">
+                            <span class=severity_class(diagnostic.severity)>
+                                {diagnostic.severity.to_string()}
+                            </span>
+                            <button class="button" on:click=move |_| on_jump(path.clone())>
+                                {location}
+                            </button>
+                            <span>{diagnostic.message}</span>
+                            <span class="text-gray">{diagnostic.rule}</span>
+                            {fix}
+                        </div>
+                    }
+                }
+            />
+
+        </div>
+    }
+}
+
 #[component]
 fn Digest(d: Memo<D>) -> impl IntoView {
     view! {
@@ -271,6 +719,19 @@ fn Digest(d: Memo<D>) -> impl IntoView {
     }
 }
 
+// Converts a `FlatArticle` to a `TreeArticle` or the other way around, storing the new nodes.
+fn convert_article<S: Store<Node, D>>(
+    store: &S,
+    article: &ObjectValue,
+) -> anyhow::Result<ObjectValue> {
+    let get_node = |digest: &D| store.get(digest);
+    let mut put = |node: Node| store.put(node);
+    match article.object_type_id {
+        FLAT_ARTICLE_TYPE_ID => flat_to_tree_article(&get_node, &mut put, article),
+        _ => tree_to_flat_article(&get_node, &mut put, article),
+    }
+}
+
 #[component]
 fn ObjectView(
     schema: ReadSignal<Schema>,
@@ -278,32 +739,65 @@ fn ObjectView(
     #[prop(into)] on_action: Callback<Action>,
     path: Memo<Path>,
     selected: RwSignal<Path>,
+    mode: RwSignal<Mode>,
+    // Error messages to display on nodes, by path.
+    errors: Memo<HashMap<Path, Vec<String>>>,
     debug: ReadSignal<bool>,
     #[prop(into)] queue_fetch: Callback<D>,
+    // Type of the field holding this node, according to the schema.
+    #[prop(optional, into)] expected_type: MaybeSignal<Option<Type>>,
+    // Type and value of the object holding this node, if any.
+    #[prop(optional, into)] parent: MaybeSignal<Option<(ObjectType, ObjectValue)>>,
 ) -> impl IntoView {
     logging::log!("rendering ObjectView {:?}", path.get_untracked());
-    let node = create_memo(move |_| get_item(&digest.get()).get());
+    let expected_type = Signal::derive(move || expected_type.get());
+    let parent = Signal::derive(move || parent.get());
+    let store = use_node_store();
+    let node = create_memo(move |_| store.get_signal(&digest.get()).get());
     let is_present = create_memo(move |_| node.get().is_some());
     let value = create_memo(move |_| node.get().unwrap().value.clone());
     let path2 = path.clone();
-    let path3 = path.clone();
     let path4 = path.clone();
     let s = create_memo(move |_| path.get() == selected.get());
+    let node_errors = create_memo(move |_| {
+        errors.with(|errors| errors.get(&path.get()).cloned().unwrap_or_default())
+    });
     fn change_value() {}
     let view_object = move |_id: Memo<ID>, v: Memo<ObjectValue>| -> HtmlElement<html::Div> {
         logging::log!("view_object {:?} {:?}", path2.get_untracked(), v.get_untracked());
+        // The schema may be edited while the document is displayed, so the type may be gone.
         let object_type = move || {
+            let object_type_id = v().object_type_id;
             schema
                 .get()
                 .object_types
-                .get(&v().object_type_id)
-                .unwrap()
-                .clone()
+                .get(&object_type_id)
+                .cloned()
+                .unwrap_or_else(|| ObjectType {
+                    name: format!("unknown type #{}", object_type_id),
+                    fields: BTreeMap::new(),
+                })
         };
         let v = v.clone();
         let v3 = v.clone();
         let path4 = path4.clone();
         let path5 = path4.clone();
+        // Field of the parent holding the other kind of article, where a conversion is stored.
+        let conversion_field_id = move || {
+            let target_type_id = match v().object_type_id {
+                FLAT_ARTICLE_TYPE_ID => TREE_ARTICLE_TYPE_ID,
+                TREE_ARTICLE_TYPE_ID => FLAT_ARTICLE_TYPE_ID,
+                _ => return None,
+            };
+            let (parent_type, _) = parent.get()?;
+            parent_type
+                .fields
+                .into_iter()
+                .find(|(_, field_type)| {
+                    field_type.type_ == Type::Object(target_type_id) && !field_type.repeated
+                })
+                .map(|(field_id, _)| field_id)
+        };
         let field_ids = move || {
             object_type()
                 .clone()
@@ -357,6 +851,53 @@ fn ObjectView(
                                 ></path>
                             </svg>
                         </button>
+                        <Show when=move || conversion_field_id().is_some()>
+                            <button
+                                class="button"
+                                title="convert between FlatArticle and TreeArticle"
+                                on:click=move |_| {
+                                    let (Some(field_id), Some((_, parent_value))) = (
+                                        conversion_field_id(),
+                                        parent.get_untracked(),
+                                    ) else {
+                                        return;
+                                    };
+                                    match convert_article(&store, &v.get_untracked()) {
+                                        Ok(article) => {
+                                            let mut parent_path = path5.get_untracked();
+                                            parent_path.pop();
+                                            let value = Value::Object(article);
+                                            // Replaces the other article of the parent, if any.
+                                            let action = if parent_value
+                                                .fields
+                                                .get(&field_id)
+                                                .is_some_and(|digests| !digests.is_empty())
+                                            {
+                                                parent_path.push(Selector { field_id, index: 0 });
+                                                Action::Update(parent_path, value)
+                                            } else {
+                                                Action::Append {
+                                                    path: parent_path,
+                                                    field_id,
+                                                    position: Position::Last,
+                                                    value,
+                                                }
+                                            };
+                                            on_action(action)
+                                        }
+                                        Err(err) => logging::warn!("cannot convert article: {}", err),
+                                    }
+                                }
+                            >
+                                {move || {
+                                    if v().object_type_id == FLAT_ARTICLE_TYPE_ID {
+                                        "to TreeArticle"
+                                    } else {
+                                        "to FlatArticle"
+                                    }
+                                }}
+                            </button>
+                        </Show>
                         <Digest d=digest />
                     </div>
                 </div>
@@ -377,6 +918,8 @@ fn ObjectView(
                         let field_type1 = field_type.clone();
                         let path4 = path4.clone();
                         let _path5 = path4.clone();
+                        // Index of the entry of this field being dragged, if any.
+                        let dragged_index = create_rw_signal(None::<usize>);
                         view! {
                             <div class="p-2">
                                 {move || field_type().name}
@@ -398,7 +941,8 @@ fn ObjectView(
                                                 .unwrap_or_default()[index]
                                                 .clone()
                                         });
-                                        let _field_type = field_type1.clone();
+                                        let field_type = field_type1.clone();
+                                        let repeated = move || field_type().repeated;
                                         let _v3 = v3.clone();
                                         let new_path = create_memo(move |_| {
                                             let mut new_path = path.get();
@@ -406,7 +950,40 @@ fn ObjectView(
                                             new_path
                                         });
                                         view! {
-                                            <div class="mx-4 my-2 flex">
+                                            <div
+                                                class="mx-4 my-2 flex"
+                                                draggable=move || if repeated() { "true" } else { "false" }
+                                                on:dragstart=move |ev| {
+                                                    ev.stop_propagation();
+                                                    dragged_index.set(Some(index));
+                                                }
+                                                on:dragend=move |_| dragged_index.set(None)
+                                                on:dragover=move |ev| {
+                                                    // Only entries of the same field are valid drop targets.
+                                                    if dragged_index.get_untracked().is_some() {
+                                                        ev.prevent_default();
+                                                    }
+                                                }
+                                                on:drop=move |ev| {
+                                                    if let Some(from) = dragged_index.get_untracked() {
+                                                        ev.prevent_default();
+                                                        ev.stop_propagation();
+                                                        dragged_index.set(None);
+                                                        let mut from_path = path.get();
+                                                        from_path.push(Selector { field_id, index: from });
+                                                        // Dropping on an entry takes its place.
+                                                        let position = if from < index {
+                                                            Position::After(index)
+                                                        } else {
+                                                            Position::Before(index)
+                                                        };
+                                                        on_action(Action::Move {
+                                                            path: from_path,
+                                                            position,
+                                                        })
+                                                    }
+                                                }
+                                            >
                                                 <Show when=move || debug()>
                                                     {format_path(&new_path.get())}
                                                 </Show>
@@ -434,12 +1011,104 @@ fn ObjectView(
                                                         ></path>
                                                     </svg>
                                                 </div>
+                                                <Show when=repeated>
+                                                    <div class="flex flex-col">
+                                                        <button
+                                                            class="cursor-pointer text-green"
+                                                            title="insert before this element"
+                                                            on:click=move |_| {
+                                                                on_action(Action::Append {
+                                                                    path: path.get(),
+                                                                    field_id,
+                                                                    position: Position::Before(index),
+                                                                    value: field_type().type_.default_value(),
+                                                                })
+                                                            }
+                                                        >
+
+                                                            <svg
+                                                                xmlns="http://www.w3.org/2000/svg"
+                                                                fill="none"
+                                                                viewBox="0 0 24 24"
+                                                                stroke-width="1.5"
+                                                                stroke="currentColor"
+                                                                class="w-4 h-4"
+                                                            >
+                                                                <path
+                                                                    stroke-linecap="round"
+                                                                    stroke-linejoin="round"
+                                                                    d="M12 4.5v15m7.5-7.5h-15"
+                                                                ></path>
+                                                            </svg>
+                                                        </button>
+                                                        <button
+                                                            class="cursor-pointer"
+                                                            title="move up"
+                                                            on:click=move |_| {
+                                                                on_action(Action::Move {
+                                                                    path: new_path.get(),
+                                                                    position: Position::Before(index.saturating_sub(1)),
+                                                                })
+                                                            }
+                                                        >
+
+                                                            <svg
+                                                                xmlns="http://www.w3.org/2000/svg"
+                                                                fill="none"
+                                                                viewBox="0 0 24 24"
+                                                                stroke-width="1.5"
+                                                                stroke="currentColor"
+                                                                class="w-4 h-4"
+                                                            >
+                                                                <path
+                                                                    stroke-linecap="round"
+                                                                    stroke-linejoin="round"
+                                                                    d="m4.5 15.75 7.5-7.5 7.5 7.5"
+                                                                ></path>
+                                                            </svg>
+                                                        </button>
+                                                        <button
+                                                            class="cursor-pointer"
+                                                            title="move down"
+                                                            on:click=move |_| {
+                                                                on_action(Action::Move {
+                                                                    path: new_path.get(),
+                                                                    position: Position::After(index + 1),
+                                                                })
+                                                            }
+                                                        >
+
+                                                            <svg
+                                                                xmlns="http://www.w3.org/2000/svg"
+                                                                fill="none"
+                                                                viewBox="0 0 24 24"
+                                                                stroke-width="1.5"
+                                                                stroke="currentColor"
+                                                                class="w-4 h-4"
+                                                            >
+                                                                <path
+                                                                    stroke-linecap="round"
+                                                                    stroke-linejoin="round"
+                                                                    d="m19.5 8.25-7.5 7.5-7.5-7.5"
+                                                                ></path>
+                                                            </svg>
+                                                        </button>
+                                                    </div>
+                                                </Show>
                                                 <div class="grow">
                                                     <ObjectView
                                                         schema=schema
                                                         digest=read_d
                                                         path=new_path
                                                         selected=selected
+                                                        mode=mode
+                                                        errors=errors
+                                                        expected_type=Signal::derive(move || {
+                                                            Some(field_type().type_)
+                                                        })
+                                                        parent=Signal::derive(move || {
+                                                            Some((object_type(), v()))
+                                                        })
                                                         on_action=on_action.clone()
                                                         debug=debug
                                                         queue_fetch=queue_fetch
@@ -486,32 +1155,11 @@ fn ObjectView(
             </div>
         }
     };
-    let view_string = move |v: Memo<String>| -> HtmlElement<html::Div> {
-        // let vv = v.to_string();
-        let _path3 = path3.clone();
-        view! {
-            <div class="w-full">
-                <input
-                    class="border border-gray-300 rounded-md shadow-sm focus:border-indigo-300 focus:ring focus:ring-indigo-200 focus:ring-opacity-50 p-2 w-full"
-                    type="text"
-                    prop:value=move || { v.get() }
-                    on:input=move |ev| {
-                        let new_value = event_target_value(&ev);
-                        on_action(Action::Update(path.get(), Value::String(new_value)));
-                    }
-                />
-
-            </div>
-        }
-    };
     let is_object = create_memo(move |_| match value.get() {
         Value::Object(_) => true,
         _ => false,
     });
-    let is_string = create_memo(move |_| match value.get() {
-        Value::String(_) => true,
-        _ => false,
-    });
+    let is_primitive = create_memo(move |_| !matches!(value.get(), Value::Object(_)));
     let _object_type_memo = create_memo(move |_| {
         logging::log!("object_type_memo {:?}", value.get());
         let object_value = match value.get() {
@@ -544,12 +1192,19 @@ fn ObjectView(
                 <div
                     class=""
                     class:selected=s
+                    class:invalid=move || !node_errors.with(Vec::is_empty)
                     on:click=move |ev| {
                         ev.stop_propagation();
                         selected.set(path.get());
                     }
                 >
 
+                    <For
+                        each=move || node_errors.get()
+                        key=|error| error.clone()
+                        children=|error| view! { <div class="text-red p-1">{error}</div> }
+                    />
+
                     <Show when=move || is_object()>
 
                         {
@@ -563,18 +1218,21 @@ fn ObjectView(
 
                     </Show>
 
-                    <Show when=move || {
-                        is_string.get()
-                    }>
-
-                        {
-                            let string_value = create_memo(move |_| match value.get() {
-                                Value::String(value) => value,
-                                _ => panic!("expected string value"),
-                            });
-                            view_string(string_value)
-                        }
-
+                    <Show when=move || is_primitive.get()>
+                        <ValueView
+                            // Fall back to the type of the value itself, e.g. for the root.
+                            expected_type=Signal::derive(move || {
+                                expected_type
+                                    .get()
+                                    .or_else(|| value.get().type_())
+                                    .unwrap_or(Type::String)
+                            })
+                            value=value
+                            path=path
+                            selected=selected
+                            mode=mode
+                            on_action=on_action
+                        />
                     </Show>
 
                 </div>
@@ -585,45 +1243,1007 @@ fn ObjectView(
 
 #[component]
 fn ValueView(
-    expected_type: FieldType,
-    value: RwSignal<Value>,
-    path: Path,
+    #[prop(into)] expected_type: Signal<Type>,
+    value: Memo<Value>,
+    path: Memo<Path>,
     selected: RwSignal<Path>,
+    mode: RwSignal<Mode>,
+    #[prop(into)] on_action: Callback<Action>,
 ) -> impl IntoView {
-    if !expected_type.type_.is_primitive() {
-        panic!("expected primitive type")
-    }
-    let text_box = view! {
-        <div>
+    let s = create_memo(move |_| path.get() == selected.get());
+    let input_ref = create_node_ref::<html::Input>();
+    // Keep the DOM focus in sync with the keyboard mode.
+    create_effect(move |_| {
+        let editing = s() && mode.get() == Mode::Edit;
+        if let Some(input) = input_ref.get() {
+            if editing {
+                let _ = input.focus();
+            } else {
+                let _ = input.blur();
+            }
+        }
+    });
+    // Text of the input. It is only written to the node when it parses as the expected type, so
+    // that invalid input never ends up in the stored value.
+    let text = create_rw_signal(String::new());
+    create_effect(move |_| {
+        let value = value.get();
+        let parsed = Value::parse(expected_type.get_untracked(), &text.get_untracked());
+        // Do not overwrite equivalent text the user is typing, e.g. "1." for 1.
+        if parsed.as_ref() != Some(&value) {
+            text.set(value.edit_text());
+        }
+    });
+    let error = create_memo(move |_| {
+        let type_ = expected_type.get();
+        let text = text.get();
+        match Value::parse(type_.clone(), &text) {
+            Some(_) => None,
+            None => Some(format!("{:?} is not a valid {}", text, type_)),
+        }
+    });
+    let on_focus = move |_| {
+        selected.set(path.get());
+        mode.set(Mode::Edit);
+    };
+    let checkbox = move || {
+        view! {
             <input
-                class="border border-gray-300 rounded-md shadow-sm focus:border-indigo-300 focus:ring focus:ring-indigo-200 focus:ring-opacity-50 block"
-                type="text"
-                prop:value=move || { value.get().to_string() }
-                on:input=move |ev| {
-                    let v = event_target_value(&ev);
-                    let parsed = Value::parse(expected_type.type_.clone(), &v);
-                    logging::log!("parsing {} as {:?} -> {:?}", v, expected_type.type_, parsed);
-                    if let Some(parsed) = parsed {
-                        value.set(parsed);
+                class="w-6 h-6 m-2"
+                type="checkbox"
+                node_ref=input_ref
+                prop:checked=move || value.get() == Value::Boolean(true)
+                on:focus=on_focus
+                on:blur=move |_| mode.set(Mode::Normal)
+                on:change=move |ev| {
+                    let checked = event_target_checked(&ev);
+                    on_action(Action::Update(path.get(), Value::Boolean(checked)));
+                }
+            />
+        }
+    };
+    let text_box = move || {
+        view! {
+            <div>
+                <input
+                    class="border border-gray-300 rounded-md shadow-sm focus:border-indigo-300 focus:ring focus:ring-indigo-200 focus:ring-opacity-50 p-2 w-full"
+                    class:border-red=move || error.get().is_some()
+                    type="text"
+                    inputmode=move || match expected_type.get() {
+                        Type::Int => "numeric",
+                        Type::Number => "decimal",
+                        _ => "text",
+                    }
+                    node_ref=input_ref
+                    prop:value=move || text.get()
+                    on:focus=on_focus
+                    on:blur=move |_| mode.set(Mode::Normal)
+                    on:input=move |ev| {
+                        let new_text = event_target_value(&ev);
+                        let parsed = Value::parse(expected_type.get_untracked(), &new_text);
+                        logging::log!("parsing {} as {:?} -> {:?}", new_text, expected_type.get_untracked(), parsed);
+                        text.set(new_text);
+                        if let Some(parsed) = parsed {
+                            on_action(Action::Update(path.get(), parsed));
+                        }
+                    }
+                />
+
+                <Show when=move || error.get().is_some()>
+                    <div class="text-red text-sm">{move || error.get()}</div>
+                </Show>
+            </div>
+        }
+    };
+    view! {
+        <div class="w-full">
+            {move || match expected_type.get() {
+                Type::Boolean => checkbox().into_view(),
+                _ => text_box().into_view(),
+            }}
+
+        </div>
+    }
+}
+
+// Formats that documents can be imported from and exported to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    CargoToml,
+    Dockerfile,
+    FlatArticleMarkdown,
+    TreeArticleMarkdown,
+    Json,
+    // The document with all the nodes it references, including its schema.
+    Bundle,
+}
+
+impl Format {
+    const ALL: &'static [Format] = &[
+        Format::CargoToml,
+        Format::Dockerfile,
+        Format::FlatArticleMarkdown,
+        Format::TreeArticleMarkdown,
+        Format::Json,
+        Format::Bundle,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Format::CargoToml => "Cargo.toml",
+            Format::Dockerfile => "Dockerfile",
+            Format::FlatArticleMarkdown => "Markdown (FlatArticle)",
+            Format::TreeArticleMarkdown => "Markdown (TreeArticle)",
+            Format::Json => "JSON",
+            Format::Bundle => "Bundle",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Format> {
+        Format::ALL
+            .iter()
+            .find(|format| format.name() == name)
+            .copied()
+    }
+
+    // Name of the file that exported documents are downloaded as.
+    fn file_name(&self) -> &'static str {
+        match self {
+            Format::CargoToml => "Cargo.toml",
+            Format::Dockerfile => "Dockerfile",
+            Format::FlatArticleMarkdown | Format::TreeArticleMarkdown => "article.md",
+            Format::Json => "document.json",
+            Format::Bundle => "document.bundle",
+        }
+    }
+
+    fn export<G: Fn(&D) -> Option<Node>>(
+        &self,
+        get_node: &G,
+        schema: &Schema,
+        root: &D,
+    ) -> anyhow::Result<String> {
+        match self {
+            Format::CargoToml => export_cargo_toml(schema, get_node, root),
+            Format::Dockerfile => export_dockerfile(schema, get_node, root),
+            // Either article type is written as Markdown.
+            Format::FlatArticleMarkdown | Format::TreeArticleMarkdown => {
+                export_markdown(get_node, root)
+            }
+            Format::Json => export_json(schema, get_node, root)
+                .and_then(|json| Ok(serde_json::to_string_pretty(&json)?)),
+            Format::Bundle => export_bundle(get_node, std::slice::from_ref(root)),
+        }
+    }
+}
+
+#[component]
+fn FormatSelect(format: ReadSignal<Format>, set_format: WriteSignal<Format>) -> impl IntoView {
+    view! {
+        <select on:change=move |ev| {
+            if let Some(new_format) = Format::from_name(&event_target_value(&ev)) {
+                set_format(new_format);
+            }
+        }>
+            {Format::ALL
+                .iter()
+                .map(|option| {
+                    view! {
+                        <option value=option.name() selected=move || format.get() == *option>
+                            {option.name()}
+                        </option>
+                    }
+                })
+                .collect_view()}
+        </select>
+    }
+}
+
+#[component]
+fn ImportPanel(
+    schema: ReadSignal<Schema>,
+    // The format, the source text, and the object type to import JSON as.
+    #[prop(into)] on_import: Callback<(Format, String, ID)>,
+    messages: ReadSignal<Vec<String>>,
+) -> impl IntoView {
+    let (format, set_format) = create_signal(Format::CargoToml);
+    let (source, set_source) = create_signal(String::new());
+    let object_types = move || {
+        let mut object_types = schema.with(|schema| {
+            schema
+                .object_types
+                .iter()
+                .map(|(id, object_type)| (*id, object_type.name.clone()))
+                .collect::<Vec<_>>()
+        });
+        object_types.sort_by(|a, b| a.1.cmp(&b.1));
+        object_types
+    };
+    let (object_type_id, set_object_type_id) = create_signal(None::<ID>);
+    // Defaults to the first type in the list.
+    let selected_type_id = move || {
+        object_type_id
+            .get()
+            .or_else(|| object_types().first().map(|(id, _)| *id))
+            .unwrap_or_default()
+    };
+    view! {
+        <div class="rounded border-solid border-2 border-blue divide-y">
+            <div class="bg-blue flex p-2 space-x-2">
+                <div>"Import"</div>
+                <FormatSelect format=format set_format=set_format/>
+                <Show when=move || format.get() == Format::Json>
+                    <select on:change=move |ev| {
+                        set_object_type_id(event_target_value(&ev).parse().ok());
+                    }>
+                        {move || {
+                            object_types()
+                                .into_iter()
+                                .map(|(id, name)| {
+                                    view! {
+                                        <option value=id selected=move || selected_type_id() == id>
+                                            {name}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+
+                    </select>
+                </Show>
+                <button
+                    class="button"
+                    on:click=move |_| on_import((format.get(), source.get(), selected_type_id()))
+                >
+                    Import
+                </button>
+            </div>
+            <textarea
+                class="w-full p-2 font-mono"
+                rows="10"
+                prop:value=source
+                on:input=move |ev| set_source(event_target_value(&ev))
+            ></textarea>
+            <For
+                each=move || messages.get()
+                key=|message| message.clone()
+                children=|message| view! { <div class="p-2">{message}</div> }
+            />
+
+        </div>
+    }
+}
+
+// The root of an imported document and the messages to show about it.
+fn import_summary<I: Imported>(import: I) -> (D, Vec<String>) {
+    (import.root().clone(), import.messages())
+}
+
+// A `data:` URL with the given text as its content, so that it can be downloaded from a link.
+fn data_url(text: &str) -> String {
+    let mut url = "data:text/plain;charset=utf-8,".to_string();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
+
+#[component]
+fn ExportPanel(schema: ReadSignal<Schema>, root: Memo<D>) -> impl IntoView {
+    let store = use_node_store();
+    let (format, set_format) = create_signal(Format::CargoToml);
+    // Exported again once nodes that are not loaded yet are read.
+    let get_node = move |digest: &D| store.get_signal(digest).get();
+    let exported = create_memo(move |_| {
+        schema.with(|schema| {
+            format
+                .get()
+                .export(&get_node, schema, &root.get())
+                .map_err(|err| err.to_string())
+        })
+    });
+    view! {
+        <div class="rounded border-solid border-2 border-blue divide-y">
+            <div class="bg-blue flex p-2 space-x-2">
+                <div>"Export"</div>
+                <FormatSelect format=format set_format=set_format/>
+                <Show when=move || exported.with(Result::is_ok)>
+                    <a
+                        class="button"
+                        download=move || format.get().file_name()
+                        href=move || exported.with(|text| data_url(text.as_deref().unwrap_or_default()))
+                    >
+                        Download
+                    </a>
+                </Show>
+            </div>
+            {move || match exported.get() {
+                Ok(text) => view! { <pre class="p-2 font-mono">{text}</pre> }.into_view(),
+                Err(err) => view! { <div class="p-2">"export failed: " {err}</div> }.into_view(),
+            }}
+
+        </div>
+    }
+}
+
+#[component]
+fn RefsPanel(
+    refs: RwSignal<Refs>,
+    root: Memo<D>,
+    #[prop(into)] on_switch: Callback<D>,
+) -> impl IntoView {
+    let (name, set_name) = create_signal(String::new());
+    let (error, set_error) = create_signal(None::<String>);
+    // Applies a change to the refs, saving them if it succeeds.
+    let update_refs = move |f: &dyn Fn(&mut Refs) -> anyhow::Result<()>| {
+        let mut new_refs = refs.get_untracked();
+        match f(&mut new_refs) {
+            Ok(()) => {
+                save_refs(&new_refs);
+                refs.set(new_refs);
+                set_error(None);
+            }
+            Err(err) => set_error(Some(err.to_string())),
+        }
+    };
+    let save = move |_| update_refs(&|refs| refs.set(&name.get_untracked(), root.get_untracked()));
+    let entries = move || {
+        refs.with(|refs| {
+            refs.iter()
+                .map(|(name, digest)| (name.clone(), digest.clone()))
+                .collect::<Vec<_>>()
+        })
+    };
+    view! {
+        <div class="rounded border-solid border-2 border-blue divide-y">
+            <div class="bg-blue flex p-2 space-x-2">
+                <div>"Refs"</div>
+                <input
+                    type="text"
+                    placeholder="name"
+                    prop:value=name
+                    on:input=move |ev| set_name(event_target_value(&ev))
+                />
+                <button class="button" on:click=save>
+                    "Save current"
+                </button>
+            </div>
+            <Show when=move || error.get().is_some()>
+                <div class="p-2">{move || error.get()}</div>
+            </Show>
+            <Show when=move || refs.with(Refs::is_empty)>
+                <div class="p-2">"No refs saved."</div>
+            </Show>
+            <For
+                each=entries
+                key=|entry| entry.clone()
+                children=move |(ref_name, digest)| {
+                    let is_current = {
+                        let digest = digest.clone();
+                        move || root.get() == digest
+                    };
+                    let open = {
+                        let digest = digest.clone();
+                        move |_| on_switch(digest.clone())
+                    };
+                    let rename = {
+                        let ref_name = ref_name.clone();
+                        move |_| update_refs(&|refs| refs.rename(&ref_name, &name.get_untracked()))
+                    };
+                    let delete = {
+                        let ref_name = ref_name.clone();
+                        move |_| {
+                            update_refs(&|refs| {
+                                refs.remove(&ref_name);
+                                Ok(())
+                            })
+                        }
+                    };
+                    view! {
+                        <div class="flex p-2 space-x-2">
+                            <div class:font-bold=is_current>{ref_name}</div>
+                            <div class="font-mono">{digest.to_hex()}</div>
+                            <button class="button" on:click=open>
+                                Open
+                            </button>
+                            <button class="button" on:click=rename>
+                                "Rename to name"
+                            </button>
+                            <button class="button" on:click=delete>
+                                Delete
+                            </button>
+                        </div>
+                    }
+                }
+            />
+
+        </div>
+    }
+}
+
+// Merges the changes made in a ref since a base ref into the current root, and lets the
+// conflicts be resolved one by one before applying the result.
+#[component]
+fn MergePanel(
+    refs: RwSignal<Refs>,
+    root: Memo<D>,
+    #[prop(into)] on_merge: Callback<D>,
+) -> impl IntoView {
+    let store = use_node_store();
+    let names = move || {
+        refs.with(|refs| {
+            refs.iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>()
+        })
+    };
+    let (base_name, set_base_name) = create_signal(None::<String>);
+    let (their_name, set_their_name) = create_signal(None::<String>);
+    let (error, set_error) = create_signal(None::<String>);
+    let merged = create_rw_signal(None::<Merge>);
+    // The side chosen for each conflict of the merge, by index.
+    let choices = create_rw_signal(Vec::<Side>::new());
+    // A merge is of the root it was started from, and would undo any edits made since.
+    create_effect(move |_| {
+        root.track();
+        merged.set(None);
+    });
+
+    let start = move |_| {
+        let digest = |name: Option<String>| {
+            let name = name.ok_or_else(|| anyhow::anyhow!("select a base and their ref"))?;
+            refs.with_untracked(|refs| refs.get(&name).cloned())
+                .ok_or_else(|| anyhow::anyhow!("ref {:?} not found", name))
+        };
+        let result = digest(base_name.get_untracked()).and_then(|base| {
+            let theirs = digest(their_name.get_untracked())?;
+            merge(&store, &base, &root.get_untracked(), &theirs)
+        });
+        match result {
+            Ok(merge) => {
+                choices.set(vec![Side::Ours; merge.conflicts.len()]);
+                merged.set(Some(merge));
+                set_error(None);
+            }
+            Err(err) => {
+                merged.set(None);
+                set_error(Some(format!("merge failed: {}", err)));
+            }
+        }
+    };
+    let apply = move |_| {
+        let Some(merge) = merged.get_untracked() else {
+            return;
+        };
+        let choices = choices.get_untracked();
+        let result = merge
+            .conflicts
+            .iter()
+            .zip(choices)
+            .try_fold(merge.root.clone(), |root, (conflict, side)| {
+                resolve_conflict(&store, &root, conflict, side)
+            });
+        match result {
+            Ok(root) => {
+                merged.set(None);
+                set_error(None);
+                on_merge(root);
+            }
+            Err(err) => set_error(Some(format!("resolving conflicts failed: {}", err))),
+        }
+    };
+    // Primitive values are shown as they are edited, objects by digest.
+    let preview = move |digests: &[D]| {
+        if digests.is_empty() {
+            return "(none)".to_string();
+        }
+        digests
+            .iter()
+            .map(|digest| match store.get(digest).map(|node| node.value) {
+                Some(Value::Object(_)) | None => digest.to_hex(),
+                Some(value) => value.edit_text(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let ref_select = move |selected: ReadSignal<Option<String>>,
+                           set_selected: WriteSignal<Option<String>>| {
+        view! {
+            <select on:change=move |ev| set_selected(Some(event_target_value(&ev)))>
+                <option value="" selected=move || selected.get().is_none()>
+                    "(select)"
+                </option>
+                {move || {
+                    names()
+                        .into_iter()
+                        .map(|name| {
+                            let value = name.clone();
+                            view! {
+                                <option
+                                    value=name.clone()
+                                    selected=move || selected.get().as_ref() == Some(&value)
+                                >
+                                    {name}
+                                </option>
+                            }
+                        })
+                        .collect_view()
+                }}
+
+            </select>
+        }
+    };
+    view! {
+        <div class="rounded border-solid border-2 border-blue divide-y">
+            <div class="bg-blue flex p-2 space-x-2">
+                <div>"Merge"</div>
+                <div>"base:"</div>
+                {ref_select(base_name, set_base_name)}
+                <div>"theirs:"</div>
+                {ref_select(their_name, set_their_name)}
+                <button class="button" on:click=start>
+                    Merge
+                </button>
+                <Show when=move || merged.with(Option::is_some)>
+                    <button class="button" on:click=apply>
+                        Apply
+                    </button>
+                </Show>
+            </div>
+            <Show when=move || error.get().is_some()>
+                <div class="p-2">{move || error.get()}</div>
+            </Show>
+            {move || {
+                merged
+                    .with(|merged| merged.as_ref().map(|merge| merge.conflicts.clone()))
+                    .map(|conflicts| {
+                        if conflicts.is_empty() {
+                            return view! { <div class="p-2">"No conflicts."</div> }.into_view();
+                        }
+                        conflicts
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, conflict)| {
+                                view! {
+                                    <div class="flex p-2 space-x-2">
+                                        <div class="font-mono">{format_path(&conflict.path)}</div>
+                                        {Side::ALL
+                                            .iter()
+                                            .map(|&side| {
+                                                let is_chosen = move || {
+                                                    choices.with(|choices| choices.get(index) == Some(&side))
+                                                };
+                                                view! {
+                                                    <button
+                                                        class="button"
+                                                        class:font-bold=is_chosen
+                                                        on:click=move |_| {
+                                                            choices.update(|choices| choices[index] = side)
+                                                        }
+                                                    >
+
+                                                        {side.name()}
+                                                        ": "
+                                                        {preview(conflict.entries(side))}
+                                                    </button>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </div>
+                                }
+                            })
+                            .collect_view()
+                    })
+            }}
+
+        </div>
+    }
+}
+
+#[component]
+fn StoragePanel(history: RwSignal<History>, refs: RwSignal<Refs>, root: Memo<D>) -> impl IntoView {
+    let store = use_node_store();
+    let pins = create_rw_signal(load_pins());
+    let (message, set_message) = create_signal(String::new());
+    // Everything that is still needed: the document being displayed, every state it can be
+    // undone or redone to, the refs and the pins.
+    let roots = move || {
+        let mut roots = history.with(|history| history.entries().to_vec());
+        roots.push(root.get());
+        roots.extend(refs.with(Refs::digests));
+        roots.extend(pins.get());
+        roots
+    };
+    let is_pinned = move || pins.with(|pins| pins.contains(&root.get()));
+    let toggle_pin = move |_| {
+        let root = root.get();
+        pins.update(|pins| {
+            if let Some(index) = pins.iter().position(|pin| *pin == root) {
+                pins.remove(index);
+            } else {
+                pins.push(root);
+            }
+            save_pins(pins);
+        });
+    };
+    // Nodes are only loaded as they are read, and marking needs all of them: a reachable node that
+    // is not loaded would otherwise be taken for garbage.
+    let scan = move |_| {
+        let roots = roots();
+        spawn_local(async move {
+            if let Err(err) = store.load_all().await {
+                set_message(format!("failed to load the store: {}", err));
+                return;
+            }
+            let garbage = find_garbage(&store, &roots);
+            set_message(format!(
+                "{} unreachable nodes, {} bytes can be reclaimed",
+                garbage.digests.len(),
+                garbage.bytes
+            ));
+        });
+    };
+    let collect = move |_| {
+        let roots = roots();
+        spawn_local(async move {
+            if let Err(err) = store.load_all().await {
+                set_message(format!("failed to load the store: {}", err));
+                return;
+            }
+            let garbage = collect_garbage(&store, &roots);
+            set_message(format!(
+                "deleted {} nodes, reclaimed {} bytes",
+                garbage.digests.len(),
+                garbage.bytes
+            ));
+        });
+    };
+    view! {
+        <div class="rounded border-solid border-2 border-blue divide-y">
+            <div class="bg-blue flex p-2 space-x-2">
+                <div>"Storage"</div>
+                <div>{move || format!("{} pinned", pins.with(Vec::len))}</div>
+                <button class="button" on:click=toggle_pin>
+                    {move || if is_pinned() { "Unpin" } else { "Pin" }}
+                </button>
+                <button class="button" on:click=scan>
+                    Scan
+                </button>
+                <button class="button" on:click=collect>
+                    "Collect garbage"
+                </button>
+            </div>
+            <div class="p-2">{message}</div>
+        </div>
+    }
+}
+
+#[component]
+fn SchemaEditor(
+    schema: ReadSignal<Schema>,
+    #[prop(into)] on_schema_action: Callback<SchemaAction>,
+) -> impl IntoView {
+    let object_type_ids = move || {
+        let mut ids = schema.with(|schema| schema.object_types.keys().cloned().collect::<Vec<_>>());
+        ids.sort();
+        ids
+    };
+    let object_type_name = move |id: ID| {
+        schema.with(|schema| {
+            schema
+                .object_types
+                .get(&id)
+                .map(|object_type| object_type.name.clone())
+                .unwrap_or_default()
+        })
+    };
+    view! {
+        <div class="rounded border-solid border-2 border-blue divide-y">
+            <div class="bg-blue flex p-2 space-x-2">
+                <div>"Schema"</div>
+                <div>"root type:"</div>
+                <select on:change=move |ev| {
+                    if let Ok(id) = event_target_value(&ev).parse::<ID>() {
+                        on_schema_action(SchemaAction::SetRootObjectType { id })
+                    }
+                }>
+                    <For
+                        each=object_type_ids
+                        key=|id| *id
+                        children=move |id| {
+                            view! {
+                                <option
+                                    value=id.to_string()
+                                    selected=move || schema.with(|schema| schema.root_object_type_id == id)
+                                >
+                                    {move || object_type_name(id)}
+                                </option>
+                            }
+                        }
+                    />
+
+                </select>
+                <button
+                    class="cursor-pointer text-green"
+                    title="add object type"
+                    on:click=move |_| {
+                        on_schema_action(SchemaAction::AddObjectType {
+                            id: new_id(),
+                            name: "NewType".to_string(),
+                        })
+                    }
+                >
+
+                    <svg
+                        xmlns="http://www.w3.org/2000/svg"
+                        fill="none"
+                        viewBox="0 0 24 24"
+                        stroke-width="1.5"
+                        stroke="currentColor"
+                        class="w-6 h-6"
+                    >
+                        <path
+                            stroke-linecap="round"
+                            stroke-linejoin="round"
+                            d="M12 9v6m3-3H9m12 0a9 9 0 1 1-18 0 9 9 0 0 1 18 0Z"
+                        ></path>
+                    </svg>
+                </button>
+            </div>
+            <For
+                each=move || {
+                    schema
+                        .with(Schema::validate)
+                        .iter()
+                        .map(|error| error.to_string())
+                        .collect::<Vec<_>>()
+                }
+
+                key=|error| error.clone()
+                children=|error| view! { <div class="text-red p-2">{error}</div> }
+            />
+            <For
+                each=object_type_ids
+                key=|id| *id
+                children=move |object_type_id| {
+                    view! {
+                        <ObjectTypeEditor
+                            schema=schema
+                            object_type_id=object_type_id
+                            on_schema_action=on_schema_action
+                        />
                     }
                 }
             />
 
         </div>
+    }
+}
+
+#[component]
+fn ObjectTypeEditor(
+    schema: ReadSignal<Schema>,
+    object_type_id: ID,
+    #[prop(into)] on_schema_action: Callback<SchemaAction>,
+) -> impl IntoView {
+    let object_type =
+        move || schema.with(|schema| schema.object_types.get(&object_type_id).cloned());
+    let field_ids = move || {
+        object_type()
+            .map(|object_type| object_type.fields.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default()
     };
-    let path1 = path.clone();
-    let s = create_memo(move |_| path1 == selected.get());
     view! {
-        <div
-            class="block"
-            class:selected=s
-            on:click=move |ev| {
-                ev.stop_propagation();
-                selected.set(path.clone());
+        <div class="p-2">
+            <div class="flex space-x-2">
+                <input
+                    class="border border-gray-300 rounded-md shadow-sm focus:border-indigo-300 focus:ring focus:ring-indigo-200 focus:ring-opacity-50 p-2"
+                    type="text"
+                    prop:value=move || object_type().map(|object_type| object_type.name).unwrap_or_default()
+                    on:change=move |ev| {
+                        on_schema_action(SchemaAction::RenameObjectType {
+                            id: object_type_id,
+                            name: event_target_value(&ev),
+                        })
+                    }
+                />
+
+                <div class="p-2">"#" {object_type_id}</div>
+                <div
+                    class="cursor-pointer text-red p-2"
+                    title="delete object type"
+                    on:click=move |_| {
+                        on_schema_action(SchemaAction::DeleteObjectType {
+                            id: object_type_id,
+                        })
+                    }
+                >
+
+                    <svg
+                        xmlns="http://www.w3.org/2000/svg"
+                        fill="none"
+                        viewBox="0 0 24 24"
+                        stroke-width="1.5"
+                        stroke="currentColor"
+                        class="w-6 h-6"
+                    >
+                        <path
+                            stroke-linecap="round"
+                            stroke-linejoin="round"
+                            d="m9.75 9.75 4.5 4.5m0-4.5-4.5 4.5M21 12a9 9 0 1 1-18 0 9 9 0 0 1 18 0Z"
+                        ></path>
+                    </svg>
+                </div>
+            </div>
+            <For
+                each=field_ids
+                key=|field_id| *field_id
+                children=move |field_id| {
+                    view! {
+                        <FieldTypeEditor
+                            schema=schema
+                            object_type_id=object_type_id
+                            field_id=field_id
+                            on_schema_action=on_schema_action
+                        />
+                    }
+                }
+            />
+
+            <button
+                class="cursor-pointer text-green mx-4"
+                title="add field"
+                on:click=move |_| {
+                    on_schema_action(SchemaAction::AddField {
+                        object_type_id,
+                        field_id: new_id(),
+                        name: "new_field".to_string(),
+                        type_: Type::String,
+                    })
+                }
+            >
+
+                <svg
+                    xmlns="http://www.w3.org/2000/svg"
+                    fill="none"
+                    viewBox="0 0 24 24"
+                    stroke-width="1.5"
+                    stroke="currentColor"
+                    class="w-6 h-6"
+                >
+                    <path
+                        stroke-linecap="round"
+                        stroke-linejoin="round"
+                        d="M12 9v6m3-3H9m12 0a9 9 0 1 1-18 0 9 9 0 0 1 18 0Z"
+                    ></path>
+                </svg>
+            </button>
+        </div>
+    }
+}
+
+#[component]
+fn FieldTypeEditor(
+    schema: ReadSignal<Schema>,
+    object_type_id: ID,
+    field_id: ID,
+    #[prop(into)] on_schema_action: Callback<SchemaAction>,
+) -> impl IntoView {
+    let field_type = move || {
+        schema.with(|schema| {
+            schema
+                .object_types
+                .get(&object_type_id)
+                .and_then(|object_type| object_type.fields.get(&field_id))
+                .cloned()
+        })
+    };
+    let selected_type_key = move || field_type().map(|field_type| type_key(&field_type.type_));
+    // Primitive types, followed by all the object types of the schema.
+    let type_options = move || {
+        let mut options = [Type::String, Type::Int, Type::Number, Type::Boolean]
+            .iter()
+            .map(|type_| (type_key(type_), type_.to_string()))
+            .collect::<Vec<_>>();
+        schema.with(|schema| {
+            let mut object_type_ids = schema.object_types.keys().cloned().collect::<Vec<_>>();
+            object_type_ids.sort();
+            for id in object_type_ids {
+                options.push((
+                    type_key(&Type::Object(id)),
+                    schema.object_types[&id].name.clone(),
+                ));
             }
-        >
+        });
+        options
+    };
+    view! {
+        <div class="mx-4 my-2 flex space-x-2">
+            <div
+                class="cursor-pointer text-red p-2"
+                title="delete field"
+                on:click=move |_| {
+                    on_schema_action(SchemaAction::DeleteField {
+                        object_type_id,
+                        field_id,
+                    })
+                }
+            >
+
+                <svg
+                    xmlns="http://www.w3.org/2000/svg"
+                    fill="none"
+                    viewBox="0 0 24 24"
+                    stroke-width="1.5"
+                    stroke="currentColor"
+                    class="w-6 h-6"
+                >
+                    <path
+                        stroke-linecap="round"
+                        stroke-linejoin="round"
+                        d="m9.75 9.75 4.5 4.5m0-4.5-4.5 4.5M21 12a9 9 0 1 1-18 0 9 9 0 0 1 18 0Z"
+                    ></path>
+                </svg>
+            </div>
+            <input
+                class="border border-gray-300 rounded-md shadow-sm focus:border-indigo-300 focus:ring focus:ring-indigo-200 focus:ring-opacity-50 p-2"
+                type="text"
+                prop:value=move || field_type().map(|field_type| field_type.name).unwrap_or_default()
+                on:change=move |ev| {
+                    on_schema_action(SchemaAction::RenameField {
+                        object_type_id,
+                        field_id,
+                        name: event_target_value(&ev),
+                    })
+                }
+            />
+
+            <select on:change=move |ev| {
+                if let Some(type_) = parse_type_key(&event_target_value(&ev)) {
+                    on_schema_action(SchemaAction::SetFieldType {
+                        object_type_id,
+                        field_id,
+                        type_,
+                    })
+                }
+            }>
+                <For
+                    each=type_options
+                    key=|option| option.clone()
+                    children=move |(key, name)| {
+                        let option_key = key.clone();
+                        view! {
+                            <option
+                                value=key
+                                selected=move || selected_type_key() == Some(option_key.clone())
+                            >
+                                {name}
+                            </option>
+                        }
+                    }
+                />
+
+            </select>
+            <label class="p-2">
+                <input
+                    type="checkbox"
+                    prop:checked=move || field_type().map(|field_type| field_type.repeated).unwrap_or_default()
+                    on:change=move |ev| {
+                        on_schema_action(SchemaAction::SetFieldRepeated {
+                            object_type_id,
+                            field_id,
+                            repeated: event_target_checked(&ev),
+                        })
+                    }
+                />
 
-            {text_box}
+                " repeated"
+            </label>
+            <div class="p-2">"#" {field_id}</div>
         </div>
     }
 }
@@ -0,0 +1,544 @@
+// Conversion between Dockerfiles and nodes of the `Dockerfile` type in `create_schema`, see
+// https://docs.docker.com/reference/dockerfile/
+//
+// Each instruction becomes a `Dockerfile Command` that sets the field named after it. Arguments in
+// shell form are kept as written, including line continuations; arguments in exec form become one
+// entry each, and set the `exec` field of the command. Comments and blank lines are not kept.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    new_id, not_imported, Imported, Node, ObjectType, ObjectValue, Schema, Type, Value, D, ID,
+};
+
+pub const DOCKERFILE_TYPE_ID: ID = 213792873;
+pub const DOCKERFILE_COMMANDS: ID = 2871232;
+
+// Each command is expected to set exactly one of these fields, which determines its instruction.
+pub const COMMAND_TYPE_ID: ID = 28973111;
+pub const COMMAND_FROM: ID = 128371;
+pub const COMMAND_RUN: ID = 89723;
+pub const COMMAND_CMD: ID = 1987312;
+pub const COMMAND_ADD: ID = 1927131;
+pub const COMMAND_COPY: ID = 23812319;
+pub const COMMAND_ENTRYPOINT: ID = 6412128;
+pub const COMMAND_USER: ID = 1298371;
+pub const COMMAND_ARG: ID = 1122811;
+pub const COMMAND_EXEC: ID = 5129873;
+
+pub const FROM_TYPE_ID: ID = 29187312;
+pub const FROM_IMAGE: ID = 281731;
+pub const FROM_TAG: ID = 976981231;
+pub const FROM_DIGEST: ID = 8912731;
+pub const FROM_AS: ID = 8197129;
+
+pub const RUN_TYPE_ID: ID = 273819273;
+pub const RUN_COMMAND: ID = 1279811;
+pub const RUN_NETWORK: ID = 9002137;
+
+pub const RUN_NETWORK_TYPE_ID: ID = 87123897;
+pub const RUN_NETWORK_DEFAULT: ID = 29187312;
+pub const RUN_NETWORK_NONE: ID = 98123981;
+pub const RUN_NETWORK_HOST: ID = 9128232;
+
+// An instruction that could not be imported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DockerfileError {
+    // 1-based number of the line on which the instruction starts.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for DockerfileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub struct DockerfileImport {
+    pub root: D,
+    pub errors: Vec<DockerfileError>,
+}
+
+impl Imported for DockerfileImport {
+    fn root(&self) -> &D {
+        &self.root
+    }
+
+    fn messages(&self) -> Vec<String> {
+        not_imported(&self.errors)
+    }
+}
+
+// Parses a Dockerfile into nodes, which are passed to `put`, and returns the digest of the root
+// `Dockerfile` node. Instructions that cannot be parsed are left out, and reported in `errors`.
+pub fn import_dockerfile<P: FnMut(Node) -> D>(
+    schema: &Schema,
+    source: &str,
+    put: &mut P,
+) -> anyhow::Result<DockerfileImport> {
+    let object_type = |id: ID| {
+        schema
+            .object_types
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("schema does not define the Dockerfile types"))
+    };
+    let mut importer = Importer {
+        command_type: object_type(COMMAND_TYPE_ID)?,
+        network_type: object_type(RUN_NETWORK_TYPE_ID)?,
+        put,
+    };
+    let mut commands = vec![];
+    let mut errors = vec![];
+    for (line, text) in logical_lines(source) {
+        match importer.command(&text) {
+            Ok(command) => commands.push(command),
+            Err(message) => errors.push(DockerfileError { line, message }),
+        }
+    }
+    let mut fields = BTreeMap::new();
+    fields.insert(DOCKERFILE_COMMANDS, commands);
+    let root = importer.object(DOCKERFILE_TYPE_ID, fields);
+    Ok(DockerfileImport { root, errors })
+}
+
+// Splits a Dockerfile into instructions, each with the number of the line it starts on. Lines
+// ending in `\` continue on the next line; the continuations are kept in the text.
+fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        // Docker also skips these within continued instructions.
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (start, text) = match current.take() {
+            Some((start, mut text)) => {
+                text.push('\n');
+                text.push_str(line.trim_end());
+                (start, text)
+            }
+            None => (index + 1, trimmed.to_string()),
+        };
+        if text.ends_with('\\') {
+            current = Some((start, text));
+        } else {
+            lines.push((start, text));
+        }
+    }
+    // A continuation on the last line.
+    if let Some((start, text)) = current {
+        lines.push((start, text.trim_end_matches('\\').trim_end().to_string()));
+    }
+    lines
+}
+
+// Joins the lines of a logical line, as Docker does before interpreting it.
+fn join_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| line.strip_suffix('\\').unwrap_or(line))
+        .collect()
+}
+
+// Skips whitespace, including line continuations.
+fn skip_space(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        match text.strip_prefix('\\') {
+            Some(rest) if rest.trim_start_matches([' ', '\t']).starts_with('\n') => text = rest,
+            _ => return text,
+        }
+    }
+}
+
+// The arguments of an instruction in exec form, `["executable", "param"]`, if they are one.
+// Anything that is not a JSON array of strings is in shell form, as in Docker.
+fn exec_form(arguments: &str) -> Option<Vec<String>> {
+    let joined = join_lines(arguments);
+    if !joined.trim_start().starts_with('[') {
+        return None;
+    }
+    serde_json::from_str(&joined).ok()
+}
+
+struct Importer<'a, P> {
+    command_type: &'a ObjectType,
+    network_type: &'a ObjectType,
+    put: &'a mut P,
+}
+
+impl<P: FnMut(Node) -> D> Importer<'_, P> {
+    fn node(&mut self, value: Value) -> D {
+        (self.put)(Node {
+            id: new_id(),
+            value,
+            schema: None,
+        })
+    }
+
+    fn object(&mut self, object_type_id: ID, fields: BTreeMap<ID, Vec<D>>) -> D {
+        self.node(Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        }))
+    }
+
+    fn string(&mut self, s: &str) -> D {
+        self.node(Value::String(s.to_string()))
+    }
+
+    fn command(&mut self, text: &str) -> Result<D, String> {
+        let (keyword, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let arguments = skip_space(arguments);
+        if arguments.is_empty() {
+            return Err(format!("{} has no arguments", keyword));
+        }
+        let name = keyword.to_lowercase();
+        let mut fields = BTreeMap::new();
+        let exec = match name.as_str() {
+            "from" => {
+                fields.insert(COMMAND_FROM, vec![self.from(arguments)?]);
+                false
+            }
+            "run" => {
+                let (network, arguments) = self.network(arguments)?;
+                let (exec, command) = self.arguments(arguments);
+                let mut run = BTreeMap::new();
+                run.insert(RUN_COMMAND, command);
+                if let Some(network) = network {
+                    run.insert(RUN_NETWORK, vec![network]);
+                }
+                fields.insert(COMMAND_RUN, vec![self.object(RUN_TYPE_ID, run)]);
+                exec
+            }
+            _ => {
+                let field_id = self
+                    .command_type
+                    .fields
+                    .iter()
+                    .find(|(_, field_type)| {
+                        field_type.name == name && matches!(field_type.type_, Type::String)
+                    })
+                    .map(|(field_id, _)| *field_id)
+                    .ok_or_else(|| format!("unknown instruction {}", keyword))?;
+                let (exec, arguments) = self.arguments(arguments);
+                fields.insert(field_id, arguments);
+                exec
+            }
+        };
+        if exec {
+            let exec = self.node(Value::Boolean(true));
+            fields.insert(COMMAND_EXEC, vec![exec]);
+        }
+        Ok(self.object(COMMAND_TYPE_ID, fields))
+    }
+
+    // Whether the arguments are in exec form, and their entries.
+    fn arguments(&mut self, arguments: &str) -> (bool, Vec<D>) {
+        match exec_form(arguments) {
+            Some(items) => (true, items.iter().map(|item| self.string(item)).collect()),
+            None => (false, vec![self.string(arguments)]),
+        }
+    }
+
+    // `FROM [--platform=<platform>] <image>[:<tag>][@<digest>] [AS <name>]`, without the
+    // platform, which the schema does not have a field for.
+    fn from(&mut self, arguments: &str) -> Result<D, String> {
+        let joined = join_lines(arguments);
+        let words: Vec<&str> = joined.split_whitespace().collect();
+        let (image, name) = match words.as_slice() {
+            [flag, ..] if flag.starts_with("--") => {
+                return Err(format!("unsupported FROM flag {}", flag));
+            }
+            [image] => (*image, None),
+            [image, as_, name] if as_.eq_ignore_ascii_case("as") => (*image, Some(*name)),
+            _ => return Err(format!("invalid FROM arguments {:?}", joined)),
+        };
+        let (image, digest) = match image.split_once('@') {
+            Some((image, digest)) => (image, Some(digest)),
+            None => (image, None),
+        };
+        // A colon before the last slash separates the port of a registry, not a tag.
+        let (image, tag) = match image.rsplit_once(':') {
+            Some((image, tag)) if !tag.contains('/') => (image, Some(tag)),
+            _ => (image, None),
+        };
+        let mut fields = BTreeMap::new();
+        for (field_id, value) in [
+            (FROM_IMAGE, Some(image)),
+            (FROM_TAG, tag),
+            (FROM_DIGEST, digest),
+            (FROM_AS, name),
+        ] {
+            if let Some(value) = value {
+                fields.insert(field_id, vec![self.string(value)]);
+            }
+        }
+        Ok(self.object(FROM_TYPE_ID, fields))
+    }
+
+    // Takes the leading `--network=<mode>` flags of a RUN instruction. Other flags are kept as
+    // part of the command.
+    fn network<'t>(&mut self, mut arguments: &'t str) -> Result<(Option<D>, &'t str), String> {
+        let mut network = None;
+        while let Some(flag) = arguments.strip_prefix("--network=") {
+            let (mode, rest) = flag.split_once(char::is_whitespace).unwrap_or((flag, ""));
+            let field_id = self
+                .network_type
+                .fields
+                .iter()
+                .find(|(_, field_type)| field_type.name == mode)
+                .map(|(field_id, _)| *field_id)
+                .ok_or_else(|| format!("unknown network mode {}", mode))?;
+            let mut fields = BTreeMap::new();
+            fields.insert(field_id, vec![self.string("")]);
+            network = Some(self.object(RUN_NETWORK_TYPE_ID, fields));
+            arguments = skip_space(rest);
+        }
+        Ok((network, arguments))
+    }
+}
+
+// Writes the `Dockerfile` document rooted at the given digest as a Dockerfile, one instruction per
+// command.
+pub fn export_dockerfile<G: Fn(&D) -> Option<Node>>(
+    schema: &Schema,
+    get_node: &G,
+    root: &D,
+) -> anyhow::Result<String> {
+    let exporter = Exporter { schema, get_node };
+    let dockerfile = exporter.object(root)?;
+    if dockerfile.object_type_id != DOCKERFILE_TYPE_ID {
+        anyhow::bail!("root is not a Dockerfile");
+    }
+    let mut out = String::new();
+    for command in dockerfile
+        .fields
+        .get(&DOCKERFILE_COMMANDS)
+        .into_iter()
+        .flatten()
+    {
+        for line in exporter.command(command)? {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+struct Exporter<'a, G> {
+    schema: &'a Schema,
+    get_node: &'a G,
+}
+
+impl<G: Fn(&D) -> Option<Node>> Exporter<'_, G> {
+    fn node(&self, digest: &D) -> anyhow::Result<Node> {
+        (self.get_node)(digest).ok_or_else(|| anyhow::anyhow!("node {} not found", digest.to_hex()))
+    }
+
+    fn object(&self, digest: &D) -> anyhow::Result<ObjectValue> {
+        match self.node(digest)?.value {
+            Value::Object(object) => Ok(object),
+            _ => anyhow::bail!("node {} is not an object", digest.to_hex()),
+        }
+    }
+
+    fn strings(&self, object: &ObjectValue, field_id: ID) -> anyhow::Result<Vec<String>> {
+        object
+            .fields
+            .get(&field_id)
+            .into_iter()
+            .flatten()
+            .map(|digest| match self.node(digest)?.value {
+                Value::String(s) => Ok(s),
+                value => anyhow::bail!("expected a string, found {}", value),
+            })
+            .collect()
+    }
+
+    fn string(&self, object: &ObjectValue, field_id: ID) -> anyhow::Result<Option<String>> {
+        Ok(self.strings(object, field_id)?.into_iter().next())
+    }
+
+    // The instructions of a command, usually exactly one.
+    fn command(&self, digest: &D) -> anyhow::Result<Vec<String>> {
+        let command = self.object(digest)?;
+        let command_type = &self.schema.object_types[&COMMAND_TYPE_ID];
+        let exec = match command.fields.get(&COMMAND_EXEC).and_then(|d| d.first()) {
+            Some(digest) => self.node(digest)?.value == Value::Boolean(true),
+            None => false,
+        };
+        let mut lines = vec![];
+        for (field_id, digests) in &command.fields {
+            if *field_id == COMMAND_EXEC || digests.is_empty() {
+                continue;
+            }
+            let field_type = command_type.fields.get(field_id).ok_or_else(|| {
+                anyhow::anyhow!("field {} is not defined in {}", field_id, command_type.name)
+            })?;
+            let keyword = field_type.name.to_uppercase();
+            match *field_id {
+                COMMAND_FROM => lines.push(format!("FROM {}", self.from(&digests[0])?)),
+                COMMAND_RUN => {
+                    for digest in digests {
+                        lines.push(format!("RUN {}", self.run(digest, exec)?));
+                    }
+                }
+                _ => lines.push(format!(
+                    "{} {}",
+                    keyword,
+                    arguments(&self.strings(&command, *field_id)?, exec)
+                )),
+            }
+        }
+        Ok(lines)
+    }
+
+    fn from(&self, digest: &D) -> anyhow::Result<String> {
+        let from = self.object(digest)?;
+        let mut out = self.string(&from, FROM_IMAGE)?.unwrap_or_default();
+        if let Some(tag) = self.string(&from, FROM_TAG)? {
+            out.push(':');
+            out.push_str(&tag);
+        }
+        if let Some(digest) = self.string(&from, FROM_DIGEST)? {
+            out.push('@');
+            out.push_str(&digest);
+        }
+        if let Some(name) = self.string(&from, FROM_AS)? {
+            out.push_str(" AS ");
+            out.push_str(&name);
+        }
+        Ok(out)
+    }
+
+    fn run(&self, digest: &D, exec: bool) -> anyhow::Result<String> {
+        let run = self.object(digest)?;
+        let mut out = String::new();
+        if let Some(network) = run.fields.get(&RUN_NETWORK).and_then(|d| d.first()) {
+            // Only the first mode is written; the lint rules report conflicting ones.
+            let network = self.object(network)?;
+            let network_type = &self.schema.object_types[&RUN_NETWORK_TYPE_ID];
+            let mode = network
+                .fields
+                .iter()
+                .find(|(_, digests)| !digests.is_empty())
+                .and_then(|(field_id, _)| network_type.fields.get(field_id));
+            if let Some(mode) = mode {
+                out.push_str(&format!("--network={} ", mode.name));
+            }
+        }
+        out.push_str(&arguments(&self.strings(&run, RUN_COMMAND)?, exec));
+        Ok(out)
+    }
+}
+
+fn arguments(arguments: &[String], exec: bool) -> String {
+    if exec {
+        let items: Vec<String> = arguments
+            .iter()
+            .map(|argument| serde_json::Value::String(argument.clone()).to_string())
+            .collect();
+        format!("[{}]", items.join(", "))
+    } else {
+        arguments.join(" ")
+    }
+}
+
+#[cfg(test)]
+use crate::{create_schema, MemoryStore, Store};
+
+#[cfg(test)]
+const TEST_DOCKERFILE: &str = r#"ARG VERSION=1.79
+FROM rust:${VERSION} AS build
+WORKDIR /app
+COPY --chown=app Cargo.toml Cargo.lock ./
+RUN --network=none cargo fetch \
+    && cargo build --release
+FROM registry.example.com:5000/debian@sha256:0123
+USER app
+ENTRYPOINT ["/app/server", "--port", "8080"]
+CMD --help
+"#;
+
+#[test]
+fn test_import_dockerfile() {
+    let source = r#"
+# syntax=docker/dockerfile:1
+from debian:12
+RUN ["apt-get", "update"]
+MAINTAINER someone
+RUN --network=bridge true
+RUN apt-get install \
+    # a comment
+    curl
+"#;
+    let schema = create_schema();
+    let store = MemoryStore::new();
+    let import = import_dockerfile(&schema, source, &mut |node: Node| store.put(node)).unwrap();
+    assert_eq!(
+        import.errors,
+        vec![
+            DockerfileError {
+                line: 5,
+                message: "unknown instruction MAINTAINER".to_string(),
+            },
+            DockerfileError {
+                line: 6,
+                message: "unknown network mode bridge".to_string(),
+            },
+        ]
+    );
+
+    let object = |digest: &D| match store.get(digest).unwrap().value {
+        Value::Object(object) => object,
+        value => panic!("expected object, found {}", value),
+    };
+    let values = |object: &ObjectValue, field_id: ID| -> Vec<Value> {
+        object.fields[&field_id]
+            .iter()
+            .map(|digest| store.get(digest).unwrap().value)
+            .collect()
+    };
+    let string = |s: &str| Value::String(s.to_string());
+
+    let commands: Vec<_> = object(&import.root).fields[&DOCKERFILE_COMMANDS]
+        .iter()
+        .map(object)
+        .collect();
+    assert_eq!(commands.len(), 3);
+    let from = object(&commands[0].fields[&COMMAND_FROM][0]);
+    assert_eq!(values(&from, FROM_IMAGE), vec![string("debian")]);
+    assert_eq!(values(&from, FROM_TAG), vec![string("12")]);
+
+    assert_eq!(
+        values(&commands[1], COMMAND_EXEC),
+        vec![Value::Boolean(true)]
+    );
+    let run = object(&commands[1].fields[&COMMAND_RUN][0]);
+    assert_eq!(
+        values(&run, RUN_COMMAND),
+        vec![string("apt-get"), string("update")]
+    );
+
+    assert!(!commands[2].fields.contains_key(&COMMAND_EXEC));
+    let run = object(&commands[2].fields[&COMMAND_RUN][0]);
+    assert_eq!(
+        values(&run, RUN_COMMAND),
+        vec![string("apt-get install \\\n    curl")]
+    );
+}
+
+#[test]
+fn test_export_dockerfile_round_trip() {
+    let schema = create_schema();
+    let store = MemoryStore::new();
+    let import =
+        import_dockerfile(&schema, TEST_DOCKERFILE, &mut |node: Node| store.put(node)).unwrap();
+    assert_eq!(import.errors, vec![]);
+    let get_node = |digest: &D| store.get(digest);
+    let exported = export_dockerfile(&schema, &get_node, &import.root).unwrap();
+    assert_eq!(exported, TEST_DOCKERFILE);
+}
@@ -0,0 +1,119 @@
+// Mark-and-sweep garbage collection of the nodes in a store.
+//
+// Every edit stores new nodes along the whole path to the root and nothing refers to the old ones
+// any more, so the store keeps growing. Nodes that cannot be reached from any root (the current
+// document, the undo history, refs, pinned digests) are garbage.
+
+use std::collections::HashSet;
+
+use leptos::SignalGetUntracked;
+
+use crate::{reachable, storage, Node, Store, D};
+
+const PINS_KEY: &str = "pins";
+
+pub struct Garbage {
+    pub digests: Vec<D>,
+    // Size of the garbage nodes when serialized.
+    pub bytes: usize,
+}
+
+// Digests of the nodes reachable from the roots, including the schemas they refer to. Nodes
+// that are not in the store are skipped.
+pub fn mark<S: Store<Node, D>>(store: &S, roots: &[D]) -> HashSet<D> {
+    // Reading from a `Store` does not fail.
+    reachable(roots, |digest| Ok(store.get(digest)))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(digest, _)| digest)
+        .collect()
+}
+
+// Finds the nodes that are not reachable from the roots, without deleting them.
+pub fn find_garbage<S: Store<Node, D>>(store: &S, roots: &[D]) -> Garbage {
+    let reachable = mark(store, roots);
+    let digests = store
+        .digests()
+        .into_iter()
+        .filter(|digest| !reachable.contains(digest))
+        .collect::<Vec<_>>();
+    let bytes = digests
+        .iter()
+        .filter_map(|digest| store.get(digest))
+        .map(|node| node.serialize().len())
+        .sum();
+    Garbage { digests, bytes }
+}
+
+// Deletes the nodes that are not reachable from the roots. The roots are marked again rather than
+// reusing an earlier `find_garbage`, since nodes stored in the meantime may have made some of the
+// garbage reachable again.
+pub fn collect_garbage<S: Store<Node, D>>(store: &S, roots: &[D]) -> Garbage {
+    let garbage = find_garbage(store, roots);
+    for digest in &garbage.digests {
+        store.remove(digest);
+    }
+    garbage
+}
+
+// Digests that are kept by garbage collection even if nothing else refers to them.
+pub fn load_pins() -> Vec<D> {
+    let serialized = storage::get_value(PINS_KEY).get_untracked();
+    serde_json::from_str(&serialized).unwrap_or_default()
+}
+
+pub fn save_pins(pins: &[D]) {
+    storage::set_value(PINS_KEY, &serde_json::to_string(pins).unwrap());
+}
+
+#[cfg(test)]
+use crate::{test_node, test_object_node, HasDigest, MemoryStore, Value};
+#[cfg(test)]
+use maplit::btreemap;
+
+#[test]
+fn test_collect_garbage() {
+    let store = MemoryStore::new();
+    let leaf = |id: u32| test_node(id, Value::Int(id as i64));
+    let schema = store.put(leaf(1));
+    let shared = store.put(leaf(2));
+    let old_child = store.put(leaf(3));
+    let new_child = store.put(leaf(4));
+    // A root with the given children in field 0.
+    let root = |children: Vec<D>| Node {
+        schema: Some(schema.clone()),
+        ..test_object_node(5, 0, btreemap! { 0 => children })
+    };
+    let old_root = store.put(root(vec![shared.clone(), old_child.clone()]));
+    let new_root = store.put(root(vec![shared.clone(), new_child.clone()]));
+    let pinned = store.put(leaf(6));
+    let unreachable = store.put(leaf(7));
+
+    let roots = [new_root.clone(), pinned.clone()];
+    let garbage = find_garbage(&store, &roots);
+    let mut expected = vec![old_root.clone(), old_child.clone(), unreachable.clone()];
+    let mut digests = garbage.digests.clone();
+    let key = |digest: &D| digest.to_hex();
+    expected.sort_by_key(key);
+    digests.sort_by_key(key);
+    assert_eq!(digests, expected);
+    let expected_bytes = [&old_root, &old_child, &unreachable]
+        .iter()
+        .map(|digest| store.get(digest).unwrap().serialize().len())
+        .sum::<usize>();
+    assert_eq!(garbage.bytes, expected_bytes);
+    // Nothing is deleted until collected.
+    assert!(store.has(&old_root));
+
+    collect_garbage(&store, &roots);
+    for digest in [&old_root, &old_child, &unreachable] {
+        assert!(!store.has(digest));
+    }
+    for digest in [&schema, &shared, &new_child, &new_root, &pinned] {
+        assert!(store.has(digest));
+    }
+    assert_eq!(
+        store.get(&new_root).map(|node| node.digest()),
+        Some(new_root)
+    );
+}
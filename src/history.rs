@@ -0,0 +1,172 @@
+use leptos::SignalGetUntracked;
+use serde::{Deserialize, Serialize};
+
+use crate::{storage, Path, D};
+
+// Oldest entries are dropped beyond this, so that the persisted history stays small.
+const MAX_ENTRIES: usize = 1000;
+
+const HISTORY_KEY: &str = "history";
+
+// Undo / redo stack of root digests. Since nodes are immutable, restoring an entry is just a
+// matter of displaying its root again.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    entries: Vec<D>,
+    // Index of the entry currently displayed.
+    current: usize,
+    // Path of the last update; further updates of the same path replace the current entry
+    // instead of creating a new one, so that typing a word is undone in a single step.
+    #[serde(skip)]
+    last_update_path: Option<Path>,
+}
+
+impl History {
+    pub fn current(&self) -> Option<&D> {
+        self.entries.get(self.current)
+    }
+
+    pub fn position(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[D] {
+        &self.entries
+    }
+
+    // Record a new root, discarding any entries that were undone.
+    pub fn push(&mut self, root: D) {
+        self.last_update_path = None;
+        if self.current() == Some(&root) {
+            return;
+        }
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.current + 1);
+        }
+        self.entries.push(root);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.current = self.entries.len() - 1;
+    }
+
+    // Record the root resulting from updating the value at the given path.
+    pub fn push_update(&mut self, path: &Path, root: D) {
+        let coalesce =
+            self.last_update_path.as_ref() == Some(path) && self.current + 1 == self.entries.len();
+        if coalesce {
+            self.entries[self.current] = root;
+        } else {
+            self.push(root);
+        }
+        self.last_update_path = Some(path.clone());
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.current + 1 < self.entries.len()
+    }
+
+    pub fn undo(&mut self) -> Option<D> {
+        self.last_update_path = None;
+        if !self.can_undo() {
+            return None;
+        }
+        self.current -= 1;
+        self.current().cloned()
+    }
+
+    pub fn redo(&mut self) -> Option<D> {
+        self.last_update_path = None;
+        if !self.can_redo() {
+            return None;
+        }
+        self.current += 1;
+        self.current().cloned()
+    }
+}
+
+pub fn load_history() -> History {
+    let serialized = storage::get_value(HISTORY_KEY).get_untracked();
+    serde_json::from_str(&serialized).unwrap_or_default()
+}
+
+pub fn save_history(history: &History) {
+    storage::set_value(HISTORY_KEY, &serde_json::to_string(history).unwrap());
+}
+
+#[cfg(test)]
+use crate::test_digest;
+
+#[test]
+fn test_history_undo_redo() {
+    let mut history = History::default();
+    assert_eq!(history.current(), None);
+    assert_eq!(history.undo(), None);
+    history.push(test_digest(1));
+    history.push(test_digest(2));
+    history.push(test_digest(3));
+    assert_eq!(history.undo(), Some(test_digest(2)));
+    assert_eq!(history.undo(), Some(test_digest(1)));
+    assert_eq!(history.undo(), None);
+    assert_eq!(history.redo(), Some(test_digest(2)));
+    // A new edit drops the entries that were undone.
+    history.push(test_digest(4));
+    assert!(!history.can_redo());
+    assert_eq!(
+        history.entries(),
+        &[test_digest(1), test_digest(2), test_digest(4)]
+    );
+    // Pushing the current root again is a no-op.
+    history.push(test_digest(4));
+    assert_eq!(history.len(), 3);
+}
+
+#[test]
+fn test_history_coalesces_updates() {
+    let path_a = vec![crate::Selector {
+        field_id: 0,
+        index: 0,
+    }];
+    let path_b = vec![crate::Selector {
+        field_id: 1,
+        index: 0,
+    }];
+    let mut history = History::default();
+    history.push(test_digest(1));
+    history.push_update(&path_a, test_digest(2));
+    history.push_update(&path_a, test_digest(3));
+    assert_eq!(history.entries(), &[test_digest(1), test_digest(3)]);
+    history.push_update(&path_b, test_digest(4));
+    assert_eq!(history.len(), 3);
+    // Undoing breaks the sequence of updates.
+    history.undo();
+    history.push_update(&path_b, test_digest(5));
+    assert_eq!(
+        history.entries(),
+        &[test_digest(1), test_digest(3), test_digest(5)]
+    );
+}
+
+#[test]
+fn test_history_serialization() {
+    let mut history = History::default();
+    history.push(test_digest(1));
+    history.push(test_digest(2));
+    history.undo();
+    let serialized = serde_json::to_string(&history).unwrap();
+    let deserialized: History = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, history);
+    assert_eq!(deserialized.current(), Some(&test_digest(1)));
+}
@@ -0,0 +1,270 @@
+// Conversion between documents of any type in a schema and plain JSON, for use with other tools.
+//
+// Objects become JSON objects keyed by field name, with arrays for repeated fields. Unlike the
+// serialization of nodes, the JSON has no digests or field ids, so documents can be read and
+// written without knowing about content addressing.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    new_id, not_imported, FieldType, Imported, Node, ObjectType, ObjectValue, Schema, Type, Value,
+    D, ID,
+};
+
+// Writes the document rooted at the given digest as JSON. Empty non-repeated fields are left out.
+pub fn export_json<G: Fn(&D) -> Option<Node>>(
+    schema: &Schema,
+    get_node: &G,
+    root: &D,
+) -> anyhow::Result<serde_json::Value> {
+    let node = get_node(root).ok_or_else(|| anyhow::anyhow!("node {} not found", root.to_hex()))?;
+    Ok(match node.value {
+        Value::Object(object) => return export_object(schema, get_node, &object),
+        Value::Empty => serde_json::Value::Null,
+        Value::String(s) => serde_json::Value::String(s),
+        Value::Int(i) => serde_json::Value::from(i),
+        Value::Number(n) => serde_json::Number::from_f64(n)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| anyhow::anyhow!("{} cannot be written as JSON", n))?,
+        Value::Boolean(b) => serde_json::Value::Bool(b),
+    })
+}
+
+fn export_object<G: Fn(&D) -> Option<Node>>(
+    schema: &Schema,
+    get_node: &G,
+    object: &ObjectValue,
+) -> anyhow::Result<serde_json::Value> {
+    let object_type = schema
+        .object_types
+        .get(&object.object_type_id)
+        .ok_or_else(|| anyhow::anyhow!("unknown object type {}", object.object_type_id))?;
+    let mut map = serde_json::Map::new();
+    for (field_id, digests) in &object.fields {
+        let field_type = object_type.fields.get(field_id).ok_or_else(|| {
+            anyhow::anyhow!("field {} is not defined in {}", field_id, object_type.name)
+        })?;
+        let value = if field_type.repeated {
+            let values = digests
+                .iter()
+                .map(|digest| export_json(schema, get_node, digest))
+                .collect::<anyhow::Result<_>>()?;
+            serde_json::Value::Array(values)
+        } else {
+            // Validation reports any further entries.
+            match digests.first() {
+                Some(digest) => export_json(schema, get_node, digest)?,
+                None => continue,
+            }
+        };
+        map.insert(field_type.name.clone(), value);
+    }
+    Ok(serde_json::Value::Object(map))
+}
+
+pub struct JsonImport {
+    pub root: D,
+    // Paths of the keys that do not match a field, e.g. "author[0].email".
+    pub unmapped: Vec<String>,
+}
+
+impl Imported for JsonImport {
+    fn root(&self) -> &D {
+        &self.root
+    }
+
+    fn messages(&self) -> Vec<String> {
+        not_imported(&self.unmapped)
+    }
+}
+
+// Reads a document of the given object type from JSON, passing its nodes to `put`. Values that
+// do not match the type of their field are an error.
+pub fn import_json<P: FnMut(Node) -> D>(
+    schema: &Schema,
+    object_type_id: ID,
+    json: &serde_json::Value,
+    put: &mut P,
+) -> anyhow::Result<JsonImport> {
+    let mut importer = Importer {
+        schema,
+        put,
+        unmapped: vec![],
+    };
+    let root = importer.object(object_type_id, json, "")?;
+    Ok(JsonImport {
+        root,
+        unmapped: importer.unmapped,
+    })
+}
+
+struct Importer<'a, P> {
+    schema: &'a Schema,
+    put: &'a mut P,
+    unmapped: Vec<String>,
+}
+
+impl<P: FnMut(Node) -> D> Importer<'_, P> {
+    fn node(&mut self, value: Value) -> D {
+        (self.put)(Node {
+            id: new_id(),
+            value,
+            schema: None,
+        })
+    }
+
+    fn object(
+        &mut self,
+        object_type_id: ID,
+        json: &serde_json::Value,
+        path: &str,
+    ) -> anyhow::Result<D> {
+        let schema = self.schema;
+        let object_type: &ObjectType = schema
+            .object_types
+            .get(&object_type_id)
+            .ok_or_else(|| anyhow::anyhow!("unknown object type {}", object_type_id))?;
+        let serde_json::Value::Object(map) = json else {
+            anyhow::bail!(
+                "{}: expected an object for {}",
+                display_path(path),
+                object_type.name
+            );
+        };
+        let mut fields = BTreeMap::new();
+        for (key, value) in map {
+            let key_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            let Some((field_id, field_type)) = object_type
+                .fields
+                .iter()
+                .find(|(_, field_type)| field_type.name == *key)
+            else {
+                self.unmapped.push(key_path);
+                continue;
+            };
+            let digests = self.field(field_type, value, &key_path)?;
+            fields.insert(*field_id, digests);
+        }
+        Ok(self.node(Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        })))
+    }
+
+    fn field(
+        &mut self,
+        field_type: &FieldType,
+        json: &serde_json::Value,
+        path: &str,
+    ) -> anyhow::Result<Vec<D>> {
+        match json {
+            serde_json::Value::Null if !field_type.repeated => Ok(vec![]),
+            serde_json::Value::Array(items) if field_type.repeated => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    self.value(&field_type.type_, item, &format!("{}[{}]", path, index))
+                })
+                .collect(),
+            _ if field_type.repeated => anyhow::bail!("{}: expected an array", path),
+            _ => Ok(vec![self.value(&field_type.type_, json, path)?]),
+        }
+    }
+
+    fn value(&mut self, type_: &Type, json: &serde_json::Value, path: &str) -> anyhow::Result<D> {
+        let value = match (type_, json) {
+            (Type::Object(object_type_id), json) => {
+                return self.object(*object_type_id, json, path)
+            }
+            (Type::String, serde_json::Value::String(s)) => Value::String(s.clone()),
+            (Type::Int, serde_json::Value::Number(n)) if n.is_i64() => {
+                Value::Int(n.as_i64().unwrap_or_default())
+            }
+            (Type::Number, serde_json::Value::Number(n)) => {
+                Value::Number(n.as_f64().unwrap_or_default())
+            }
+            (Type::Boolean, serde_json::Value::Bool(b)) => Value::Boolean(*b),
+            (type_, json) => anyhow::bail!("{}: expected {:?}, found {}", path, type_, json),
+        };
+        Ok(self.node(value))
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "(root)"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+use crate::{create_schema, MemoryStore, Store};
+
+#[cfg(test)]
+const TEST_POST_TYPE_ID: ID = 2325;
+
+#[cfg(test)]
+fn test_json_round_trip(
+    json: &serde_json::Value,
+) -> anyhow::Result<(JsonImport, serde_json::Value)> {
+    let schema = create_schema();
+    let store = MemoryStore::new();
+    let import = import_json(&schema, TEST_POST_TYPE_ID, json, &mut |node: Node| {
+        store.put(node)
+    })?;
+    let exported = export_json(&schema, &|digest: &D| store.get(digest), &import.root)?;
+    Ok((import, exported))
+}
+
+#[test]
+fn test_json_import_export() {
+    let json = serde_json::json!({
+        "title": "Hello",
+        "author": [
+            { "name": "Ada", "age": 36, "is_admin": true, "friends": ["Charles"] },
+        ],
+        "comments": [],
+    });
+    let (import, exported) = test_json_round_trip(&json).unwrap();
+    assert!(import.unmapped.is_empty());
+    assert_eq!(exported, json);
+
+    let json = serde_json::json!({
+        "title": "Hello",
+        "content": null,
+        "author": [{ "name": "Ada", "email": "ada@example.com" }],
+    });
+    let (import, exported) = test_json_round_trip(&json).unwrap();
+    assert_eq!(import.unmapped, vec!["author[0].email"]);
+    assert_eq!(
+        exported,
+        serde_json::json!({ "title": "Hello", "author": [{ "name": "Ada" }] })
+    );
+}
+
+#[test]
+fn test_json_import_type_mismatch() {
+    let errors = [
+        serde_json::json!([]),
+        serde_json::json!({ "title": 1 }),
+        serde_json::json!({ "author": { "name": "Ada" } }),
+        serde_json::json!({ "author": [{ "age": 1.5 }] }),
+    ]
+    .iter()
+    .map(|json| test_json_round_trip(json).err().map(|err| err.to_string()))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            Some("(root): expected an object for Post".to_string()),
+            Some("title: expected String, found 1".to_string()),
+            Some("author: expected an array".to_string()),
+            Some("author[0].age: expected Int, found 1.5".to_string()),
+        ]
+    );
+}
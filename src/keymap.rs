@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use leptos::SignalGetUntracked;
+use serde::{Deserialize, Serialize};
+
+use crate::{storage, ENTER_KEY, ESCAPE_KEY};
+
+const KEYMAP_KEY: &str = "keymap";
+
+pub const LEFT_KEY: u32 = 37;
+pub const UP_KEY: u32 = 38;
+pub const RIGHT_KEY: u32 = 39;
+pub const DOWN_KEY: u32 = 40;
+
+// Key codes of letter keys are the ones of their upper case ASCII character.
+pub const fn letter_key(c: char) -> u32 {
+    c.to_ascii_uppercase() as u32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Mode {
+    // Keys move the selection and operate on the selected node.
+    Normal,
+    // A primitive value is being edited, keys go to the input element.
+    Edit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Key {
+    pub code: u32,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl Key {
+    pub fn plain(code: u32) -> Self {
+        Key {
+            code,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    pub fn ctrl(code: u32) -> Self {
+        Key {
+            code,
+            ctrl: true,
+            shift: false,
+        }
+    }
+
+    pub fn shift(code: u32) -> Self {
+        Key {
+            code,
+            ctrl: false,
+            shift: true,
+        }
+    }
+
+    pub fn from_event(ev: &leptos::ev::KeyboardEvent) -> Self {
+        Key {
+            code: ev.key_code(),
+            // Treat Cmd on macOS like Ctrl.
+            ctrl: ev.ctrl_key() || ev.meta_key(),
+            shift: ev.shift_key(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Command {
+    SelectParent,
+    SelectChild,
+    SelectPrev,
+    SelectNext,
+    StartEditing,
+    StopEditing,
+    // Append a new entry after the selected one, in the same field.
+    Append,
+    // Insert a new entry before the selected one, in the same field.
+    InsertBefore,
+    // Move the selected entry within its field.
+    MoveUp,
+    MoveDown,
+    Delete,
+    Focus,
+    Undo,
+    Redo,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: HashMap<(Mode, Key), Command>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Keymap {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn bind(&mut self, mode: Mode, key: Key, command: Command) {
+        self.bindings.insert((mode, key), command);
+    }
+
+    pub fn unbind(&mut self, mode: Mode, key: Key) {
+        self.bindings.remove(&(mode, key));
+    }
+
+    pub fn command(&self, mode: Mode, key: Key) -> Option<Command> {
+        self.bindings.get(&(mode, key)).cloned()
+    }
+
+    pub fn apply(&mut self, bindings: &[Binding]) {
+        for binding in bindings {
+            match binding.command {
+                Some(command) => self.bind(binding.mode, binding.key, command),
+                None => self.unbind(binding.mode, binding.key),
+            }
+        }
+    }
+}
+
+// A change to the default keymap.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub mode: Mode,
+    pub key: Key,
+    // The key does nothing if there is no command.
+    pub command: Option<Command>,
+}
+
+// The default keymap with the user's customisations, which are stored as a list of bindings.
+pub fn load_keymap() -> Keymap {
+    let serialized = storage::get_value(KEYMAP_KEY).get_untracked();
+    let bindings: Vec<Binding> = serde_json::from_str(&serialized).unwrap_or_default();
+    let mut keymap = Keymap::default();
+    keymap.apply(&bindings);
+    keymap
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap::new();
+        for (codes, command) in [
+            ([LEFT_KEY, letter_key('h')], Command::SelectParent),
+            ([RIGHT_KEY, letter_key('l')], Command::SelectChild),
+            ([UP_KEY, letter_key('k')], Command::SelectPrev),
+            ([DOWN_KEY, letter_key('j')], Command::SelectNext),
+        ] {
+            for code in codes {
+                keymap.bind(Mode::Normal, Key::plain(code), command);
+            }
+        }
+        keymap.bind(Mode::Normal, Key::plain(ENTER_KEY), Command::StartEditing);
+        keymap.bind(Mode::Normal, Key::plain(letter_key('a')), Command::Append);
+        keymap.bind(
+            Mode::Normal,
+            Key::plain(letter_key('i')),
+            Command::InsertBefore,
+        );
+        for code in [UP_KEY, letter_key('k')] {
+            keymap.bind(Mode::Normal, Key::shift(code), Command::MoveUp);
+        }
+        for code in [DOWN_KEY, letter_key('j')] {
+            keymap.bind(Mode::Normal, Key::shift(code), Command::MoveDown);
+        }
+        keymap.bind(Mode::Normal, Key::plain(letter_key('d')), Command::Delete);
+        keymap.bind(Mode::Normal, Key::plain(letter_key('f')), Command::Focus);
+        keymap.bind(Mode::Normal, Key::plain(letter_key('u')), Command::Undo);
+        keymap.bind(Mode::Normal, Key::ctrl(letter_key('z')), Command::Undo);
+        keymap.bind(Mode::Normal, Key::ctrl(letter_key('y')), Command::Redo);
+        keymap.bind(
+            Mode::Normal,
+            Key {
+                code: letter_key('z'),
+                ctrl: true,
+                shift: true,
+            },
+            Command::Redo,
+        );
+        keymap.bind(Mode::Edit, Key::plain(ESCAPE_KEY), Command::StopEditing);
+        keymap
+    }
+}
+
+#[test]
+fn test_default_keymap() {
+    let keymap = Keymap::default();
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::plain(letter_key('j'))),
+        Some(Command::SelectNext)
+    );
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::plain(UP_KEY)),
+        Some(Command::SelectPrev)
+    );
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::plain(ENTER_KEY)),
+        Some(Command::StartEditing)
+    );
+    assert_eq!(
+        keymap.command(Mode::Edit, Key::plain(ESCAPE_KEY)),
+        Some(Command::StopEditing)
+    );
+    // Typing in edit mode must not move the selection.
+    assert_eq!(
+        keymap.command(Mode::Edit, Key::plain(letter_key('j'))),
+        None
+    );
+    // Modifiers are part of the key.
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::ctrl(letter_key('d'))),
+        None
+    );
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::ctrl(letter_key('z'))),
+        Some(Command::Undo)
+    );
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::shift(letter_key('j'))),
+        Some(Command::MoveDown)
+    );
+}
+
+#[test]
+fn test_custom_keymap() {
+    let mut keymap = Keymap::default();
+    let bindings = serde_json::from_value::<Vec<Binding>>(serde_json::json!([
+        {
+            "mode": "Normal",
+            "key": { "code": letter_key('d'), "ctrl": false, "shift": false },
+            "command": null,
+        },
+        {
+            "mode": "Normal",
+            "key": { "code": letter_key('x'), "ctrl": false, "shift": false },
+            "command": "Delete",
+        },
+    ]))
+    .unwrap();
+    keymap.apply(&bindings);
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::plain(letter_key('d'))),
+        None
+    );
+    assert_eq!(
+        keymap.command(Mode::Normal, Key::plain(letter_key('x'))),
+        Some(Command::Delete)
+    );
+}
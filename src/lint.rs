@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+mod cargo;
+mod dockerfile;
+
+use crate::{Action, Node, ObjectType, ObjectValue, Path, Schema, Selector, Value, D, ID};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+// A change to the document that resolves a diagnostic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    pub description: String,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    // Name of the rule that emitted the diagnostic, filled in by the registry.
+    pub rule: &'static str,
+    pub severity: Severity,
+    // Path of the offending node, relative to the root of the document.
+    pub path: Path,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, path: &Path, message: impl Into<String>) -> Self {
+        Diagnostic {
+            rule: "",
+            severity,
+            path: path.clone(),
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    pub fn with_fix(mut self, description: impl Into<String>, action: Action) -> Self {
+        self.fix = Some(Fix {
+            description: description.into(),
+            action,
+        });
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.severity, self.message, self.rule)
+    }
+}
+
+// Gives rules access to the nodes referenced by the object being checked.
+pub struct LintContext<'a> {
+    get_node: &'a dyn Fn(&D) -> Option<Node>,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn new(get_node: &'a dyn Fn(&D) -> Option<Node>) -> Self {
+        LintContext { get_node }
+    }
+
+    pub fn node(&self, digest: &D) -> Option<Node> {
+        (self.get_node)(digest)
+    }
+
+    // Values of all the entries of a field, skipping missing nodes.
+    pub fn values(&self, object: &ObjectValue, field_id: ID) -> Vec<Value> {
+        object
+            .fields
+            .get(&field_id)
+            .map(|digests| {
+                digests
+                    .iter()
+                    .filter_map(|digest| self.node(digest))
+                    .map(|node| node.value)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Value of the first entry of a field.
+    pub fn value(&self, object: &ObjectValue, field_id: ID) -> Option<Value> {
+        let digest = object.fields.get(&field_id)?.first()?;
+        self.node(digest).map(|node| node.value)
+    }
+
+    pub fn string(&self, object: &ObjectValue, field_id: ID) -> Option<String> {
+        match self.value(object, field_id)? {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // Like `string`, but treats empty strings, the default for new fields, as absent.
+    pub fn non_empty_string(&self, object: &ObjectValue, field_id: ID) -> Option<String> {
+        self.string(object, field_id).filter(|s| !s.is_empty())
+    }
+
+    pub fn object(&self, digest: &D) -> Option<ObjectValue> {
+        match self.node(digest)?.value {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+}
+
+// Path of the entry at the given index of a field of the object at `path`.
+pub fn field_path(path: &Path, field_id: ID, index: usize) -> Path {
+    let mut path = path.clone();
+    path.push(Selector { field_id, index });
+    path
+}
+
+pub trait LintRule {
+    fn name(&self) -> &'static str;
+    // Checks an object of a type the rule is registered for. `path` is the path of the object.
+    fn check(
+        &self,
+        cx: &LintContext,
+        object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic>;
+}
+
+// Lint rules, keyed by the id of the object type they apply to.
+#[derive(Default)]
+pub struct LintRegistry {
+    rules: HashMap<ID, Vec<Box<dyn LintRule>>>,
+}
+
+impl LintRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<R: LintRule + 'static>(&mut self, object_type_id: ID, rule: R) {
+        self.rules
+            .entry(object_type_id)
+            .or_default()
+            .push(Box::new(rule));
+    }
+
+    pub fn rules(&self, object_type_id: ID) -> &[Box<dyn LintRule>] {
+        self.rules
+            .get(&object_type_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // Runs the registered rules on every object of the document rooted at the given digest.
+    // Objects whose type is not in the schema are skipped, since validation reports those.
+    pub fn run<G: Fn(&D) -> Option<Node>>(
+        &self,
+        schema: &Schema,
+        get_node: &G,
+        root_digest: &D,
+    ) -> Vec<Diagnostic> {
+        let cx = LintContext::new(get_node);
+        let mut diagnostics = vec![];
+        self.run_node(schema, &cx, root_digest, &Path::default(), &mut diagnostics);
+        diagnostics
+    }
+
+    fn run_node(
+        &self,
+        schema: &Schema,
+        cx: &LintContext,
+        digest: &D,
+        path: &Path,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let Some(object) = cx.object(digest) else {
+            return;
+        };
+        if let Some(object_type) = schema.object_types.get(&object.object_type_id) {
+            for rule in self.rules(object.object_type_id) {
+                for mut diagnostic in rule.check(cx, object_type, &object, path) {
+                    diagnostic.rule = rule.name();
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        for (field_id, digests) in object.fields.iter() {
+            for (index, child_digest) in digests.iter().enumerate() {
+                let child_path = field_path(path, *field_id, index);
+                self.run_node(schema, cx, child_digest, &child_path, diagnostics);
+            }
+        }
+    }
+}
+
+const USER_TYPE_ID: ID = 27092;
+const USER_AGE_FIELD_ID: ID = 1;
+
+// The built-in rules, for the types in `create_schema`.
+pub fn default_lint_registry() -> LintRegistry {
+    let mut registry = LintRegistry::new();
+    registry.register(USER_TYPE_ID, NegativeAge);
+    cargo::register(&mut registry);
+    dockerfile::register(&mut registry);
+    registry
+}
+
+struct NegativeAge;
+
+impl LintRule for NegativeAge {
+    fn name(&self) -> &'static str {
+        "negative-age"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        match cx.value(object, USER_AGE_FIELD_ID) {
+            Some(Value::Int(age)) if age < 0 => {
+                let age_path = field_path(path, USER_AGE_FIELD_ID, 0);
+                vec![
+                    Diagnostic::new(Severity::Error, &age_path, "age must not be negative")
+                        .with_fix(
+                            "set age to 0",
+                            Action::Update(age_path.clone(), Value::Int(0)),
+                        ),
+                ]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{create_schema, test_node, test_object_node, MemoryStore, Store};
+#[cfg(test)]
+use maplit::btreemap;
+
+#[test]
+fn test_lint_registry_runs_rules_by_object_type() {
+    let store = MemoryStore::new();
+    let age = store.put(test_node(0, Value::Int(-3)));
+    let user = store.put(test_object_node(
+        0,
+        USER_TYPE_ID,
+        btreemap! { USER_AGE_FIELD_ID => vec![age] },
+    ));
+    // Same fields, but a type the rule is not registered for.
+    let other = store.put(test_object_node(
+        0,
+        28398,
+        btreemap! { USER_AGE_FIELD_ID => vec![user.clone()] },
+    ));
+    let root = store.put(test_object_node(
+        0,
+        298732,
+        btreemap! { 37438 => vec![other, user] },
+    ));
+    let get_node = |d: &D| store.get(d);
+    let diagnostics = default_lint_registry().run(&create_schema(), &get_node, &root);
+
+    let age_paths = [
+        vec![
+            Selector {
+                field_id: 37438,
+                index: 0,
+            },
+            Selector {
+                field_id: USER_AGE_FIELD_ID,
+                index: 0,
+            },
+            Selector {
+                field_id: USER_AGE_FIELD_ID,
+                index: 0,
+            },
+        ],
+        vec![
+            Selector {
+                field_id: 37438,
+                index: 1,
+            },
+            Selector {
+                field_id: USER_AGE_FIELD_ID,
+                index: 0,
+            },
+        ],
+    ];
+    assert_eq!(
+        diagnostics,
+        age_paths
+            .iter()
+            .map(|path| Diagnostic {
+                rule: "negative-age",
+                severity: Severity::Error,
+                path: path.clone(),
+                message: "age must not be negative".to_string(),
+                fix: Some(Fix {
+                    description: "set age to 0".to_string(),
+                    action: Action::Update(path.clone(), Value::Int(0)),
+                }),
+            })
+            .collect::<Vec<_>>()
+    );
+}
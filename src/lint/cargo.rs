@@ -0,0 +1,407 @@
+// Rules for the `CargoManifest` types in `create_schema`, see
+// https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html
+
+use std::collections::HashMap;
+
+use super::{field_path, Diagnostic, LintContext, LintRegistry, LintRule, Severity};
+use crate::{Action, ObjectType, ObjectValue, Path, Position, Value, CARGO_MANIFEST_TYPE_ID, ID};
+
+pub const MANIFEST_DEPENDENCIES: ID = 1;
+pub const MANIFEST_DEV_DEPENDENCIES: ID = 2;
+
+pub const PACKAGE_TYPE_ID: ID = 87839159;
+pub const PACKAGE_VERSION: ID = 1;
+pub const PACKAGE_EDITION: ID = 10;
+
+pub const DEPENDENCY_TYPE_ID: ID = 7218972;
+pub const DEPENDENCY_NAME: ID = 12093;
+pub const DEPENDENCY_VERSION: ID = 912831;
+pub const DEPENDENCY_GIT: ID = 2098713;
+pub const DEPENDENCY_BRANCH: ID = 912783;
+pub const DEPENDENCY_TAG: ID = 341231;
+pub const DEPENDENCY_REV: ID = 123901;
+pub const DEPENDENCY_PATH: ID = 198293;
+
+const DEFAULT_EDITION: &str = "2021";
+
+pub fn register(registry: &mut LintRegistry) {
+    registry.register(CARGO_MANIFEST_TYPE_ID, DuplicateDependency);
+    registry.register(PACKAGE_TYPE_ID, MissingEdition);
+    registry.register(PACKAGE_TYPE_ID, InvalidPackageVersion);
+    registry.register(DEPENDENCY_TYPE_ID, WildcardVersion);
+    registry.register(DEPENDENCY_TYPE_ID, GitAndPath);
+    registry.register(DEPENDENCY_TYPE_ID, GitRefWithoutGit);
+}
+
+struct WildcardVersion;
+
+impl LintRule for WildcardVersion {
+    fn name(&self) -> &'static str {
+        "wildcard-version"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        match cx.string(object, DEPENDENCY_VERSION) {
+            Some(version) if version.trim() == "*" => vec![Diagnostic::new(
+                Severity::Warning,
+                &field_path(path, DEPENDENCY_VERSION, 0),
+                "wildcard version requirement; any future version may be picked",
+            )],
+            _ => vec![],
+        }
+    }
+}
+
+struct GitAndPath;
+
+impl LintRule for GitAndPath {
+    fn name(&self) -> &'static str {
+        "git-and-path"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        if cx.non_empty_string(object, DEPENDENCY_GIT).is_some()
+            && cx.non_empty_string(object, DEPENDENCY_PATH).is_some()
+        {
+            vec![Diagnostic::new(
+                Severity::Error,
+                path,
+                "dependency specifies both git and path",
+            )]
+        } else {
+            vec![]
+        }
+    }
+}
+
+struct GitRefWithoutGit;
+
+impl LintRule for GitRefWithoutGit {
+    fn name(&self) -> &'static str {
+        "git-ref-without-git"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        if cx.non_empty_string(object, DEPENDENCY_GIT).is_some() {
+            return vec![];
+        }
+        [DEPENDENCY_BRANCH, DEPENDENCY_TAG, DEPENDENCY_REV]
+            .into_iter()
+            .filter(|field_id| cx.non_empty_string(object, *field_id).is_some())
+            .map(|field_id| {
+                let name = object_type
+                    .fields
+                    .get(&field_id)
+                    .map(|field_type| field_type.name.clone())
+                    .unwrap_or_default();
+                let ref_path = field_path(path, field_id, 0);
+                Diagnostic::new(
+                    Severity::Error,
+                    &ref_path,
+                    format!("{} is only meaningful for git dependencies", name),
+                )
+                .with_fix(
+                    format!("remove {}", name),
+                    Action::Delete { path: ref_path },
+                )
+            })
+            .collect()
+    }
+}
+
+struct MissingEdition;
+
+impl LintRule for MissingEdition {
+    fn name(&self) -> &'static str {
+        "missing-edition"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        if cx.non_empty_string(object, PACKAGE_EDITION).is_some() {
+            return vec![];
+        }
+        let description = format!("set edition to {}", DEFAULT_EDITION);
+        let value = Value::String(DEFAULT_EDITION.to_string());
+        // The field may be present but empty, in which case it is updated in place.
+        let action = if object
+            .fields
+            .get(&PACKAGE_EDITION)
+            .is_some_and(|v| !v.is_empty())
+        {
+            Action::Update(field_path(path, PACKAGE_EDITION, 0), value)
+        } else {
+            Action::Append {
+                path: path.clone(),
+                field_id: PACKAGE_EDITION,
+                position: Position::Last,
+                value,
+            }
+        };
+        vec![Diagnostic::new(
+            Severity::Warning,
+            path,
+            "package does not specify an edition, so it defaults to 2015",
+        )
+        .with_fix(description, action)]
+    }
+}
+
+struct InvalidPackageVersion;
+
+impl LintRule for InvalidPackageVersion {
+    fn name(&self) -> &'static str {
+        "invalid-package-version"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        match cx.string(object, PACKAGE_VERSION) {
+            Some(version) if !is_semver(&version) => vec![Diagnostic::new(
+                Severity::Error,
+                &field_path(path, PACKAGE_VERSION, 0),
+                format!("{:?} is not a valid semver version", version),
+            )],
+            _ => vec![],
+        }
+    }
+}
+
+// Whether the string is a version as defined by https://semver.org, e.g. "1.0.0-alpha.1+build".
+fn is_semver(version: &str) -> bool {
+    let numeric = |s: &str| {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) && (s == "0" || !s.starts_with('0'))
+    };
+    let identifier =
+        |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let (version, build) = match version.split_once('+') {
+        Some((version, build)) => (version, Some(build)),
+        None => (version, None),
+    };
+    let (core, pre) = match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    };
+    let core: Vec<&str> = core.split('.').collect();
+    core.len() == 3
+        && core.iter().all(|part| numeric(part))
+        && match pre {
+            Some(pre) => pre.split('.').all(|part| {
+                identifier(part) && (numeric(part) || !part.chars().all(|c| c.is_ascii_digit()))
+            }),
+            None => true,
+        }
+        && match build {
+            Some(build) => build.split('.').all(identifier),
+            None => true,
+        }
+}
+
+struct DuplicateDependency;
+
+impl LintRule for DuplicateDependency {
+    fn name(&self) -> &'static str {
+        "duplicate-dependency"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        let field_name = |field_id: ID| {
+            object_type
+                .fields
+                .get(&field_id)
+                .map(|field_type| field_type.name.clone())
+                .unwrap_or_default()
+        };
+        let mut diagnostics = vec![];
+        // Field in which each dependency name was first seen.
+        let mut seen = HashMap::<String, ID>::new();
+        for field_id in [MANIFEST_DEPENDENCIES, MANIFEST_DEV_DEPENDENCIES] {
+            let digests = object.fields.get(&field_id).cloned().unwrap_or_default();
+            for (index, digest) in digests.iter().enumerate() {
+                let Some(name) = cx
+                    .object(digest)
+                    .and_then(|dependency| cx.non_empty_string(&dependency, DEPENDENCY_NAME))
+                else {
+                    continue;
+                };
+                let Some(first_field_id) = seen.get(&name) else {
+                    seen.insert(name, field_id);
+                    continue;
+                };
+                // Within the same table this is not even valid TOML, across tables it may be
+                // intended, e.g. to enable more features in tests.
+                let severity = if *first_field_id == field_id {
+                    Severity::Error
+                } else {
+                    Severity::Info
+                };
+                diagnostics.push(Diagnostic::new(
+                    severity,
+                    &field_path(path, field_id, index),
+                    format!(
+                        "dependency {:?} is already listed in {}",
+                        name,
+                        field_name(*first_field_id)
+                    ),
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+use crate::{create_schema, test_object_node, test_string_node, MemoryStore, Selector, Store, D};
+#[cfg(test)]
+use maplit::btreemap;
+
+#[cfg(test)]
+const MANIFEST_PACKAGE: ID = 0;
+
+#[test]
+fn test_is_semver() {
+    for version in [
+        "0.1.0",
+        "1.2.3-alpha.1",
+        "1.0.0-0.3.7",
+        "1.0.0+20130313144700",
+        "1.0.0-x-y",
+    ] {
+        assert!(is_semver(version), "{}", version);
+    }
+    for version in [
+        "", "1", "1.2", "01.2.3", "1.2.3-", "1.2.3-01", "1.2.3+", "1.2.x", "v1.2.3",
+    ] {
+        assert!(!is_semver(version), "{}", version);
+    }
+}
+
+#[test]
+fn test_cargo_rules() {
+    let store = MemoryStore::new();
+    let name = store.put(test_string_node(0, "serde"));
+    let version = store.put(test_string_node(0, "*"));
+    let git = store.put(test_string_node(0, "https://github.com/serde-rs/serde"));
+    let local = store.put(test_string_node(0, "../serde"));
+    let branch = store.put(test_string_node(0, "main"));
+    let serde = store.put(test_object_node(
+        0,
+        DEPENDENCY_TYPE_ID,
+        btreemap! {
+            DEPENDENCY_NAME => vec![name.clone()],
+            DEPENDENCY_VERSION => vec![version],
+            DEPENDENCY_GIT => vec![git],
+            DEPENDENCY_PATH => vec![local],
+        },
+    ));
+    let serde_branch = store.put(test_object_node(
+        0,
+        DEPENDENCY_TYPE_ID,
+        btreemap! {
+            DEPENDENCY_NAME => vec![name],
+            DEPENDENCY_BRANCH => vec![branch],
+        },
+    ));
+    let package_version = store.put(test_string_node(0, "1.0"));
+    let package = store.put(test_object_node(
+        0,
+        PACKAGE_TYPE_ID,
+        btreemap! { PACKAGE_VERSION => vec![package_version] },
+    ));
+    let manifest = store.put(test_object_node(
+        0,
+        CARGO_MANIFEST_TYPE_ID,
+        btreemap! {
+            MANIFEST_PACKAGE => vec![package],
+            MANIFEST_DEPENDENCIES => vec![serde],
+            MANIFEST_DEV_DEPENDENCIES => vec![serde_branch],
+        },
+    ));
+    let get_node = |d: &D| store.get(d);
+    let diagnostics = super::default_lint_registry().run(&create_schema(), &get_node, &manifest);
+
+    let sel = |field_id, index| Selector { field_id, index };
+    let package_path = vec![sel(MANIFEST_PACKAGE, 0)];
+    let serde_path = vec![sel(MANIFEST_DEPENDENCIES, 0)];
+    let serde_branch_path = vec![sel(MANIFEST_DEV_DEPENDENCIES, 0)];
+    let summary: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.rule,
+                diagnostic.severity,
+                diagnostic.path.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "duplicate-dependency",
+                Severity::Info,
+                serde_branch_path.clone()
+            ),
+            ("missing-edition", Severity::Warning, package_path.clone()),
+            (
+                "invalid-package-version",
+                Severity::Error,
+                field_path(&package_path, PACKAGE_VERSION, 0)
+            ),
+            (
+                "wildcard-version",
+                Severity::Warning,
+                field_path(&serde_path, DEPENDENCY_VERSION, 0)
+            ),
+            ("git-and-path", Severity::Error, serde_path),
+            (
+                "git-ref-without-git",
+                Severity::Error,
+                field_path(&serde_branch_path, DEPENDENCY_BRANCH, 0)
+            ),
+        ]
+    );
+    assert_eq!(
+        diagnostics[1].fix.as_ref().map(|fix| &fix.action),
+        Some(&Action::Append {
+            path: package_path,
+            field_id: PACKAGE_EDITION,
+            position: Position::Last,
+            value: Value::String("2021".to_string()),
+        })
+    );
+}
@@ -0,0 +1,462 @@
+// Rules for the `Dockerfile` types in `create_schema`, along the lines of the ones in
+// https://github.com/hadolint/hadolint
+
+use super::{field_path, Diagnostic, LintContext, LintRegistry, LintRule, Severity};
+use crate::{
+    Action, ObjectType, ObjectValue, Path, Value, COMMAND_ADD, COMMAND_ARG, COMMAND_CMD,
+    COMMAND_COPY, COMMAND_ENTRYPOINT, COMMAND_FROM, COMMAND_TYPE_ID, COMMAND_USER,
+    DOCKERFILE_COMMANDS, DOCKERFILE_TYPE_ID, FROM_DIGEST, FROM_IMAGE, FROM_TAG, FROM_TYPE_ID, ID,
+    RUN_NETWORK_DEFAULT, RUN_NETWORK_HOST, RUN_NETWORK_NONE, RUN_NETWORK_TYPE_ID,
+};
+
+pub fn register(registry: &mut LintRegistry) {
+    registry.register(DOCKERFILE_TYPE_ID, FirstCommandNotFrom);
+    registry.register(
+        DOCKERFILE_TYPE_ID,
+        MultipleInstructions {
+            name: "multiple-cmd",
+            field_id: COMMAND_CMD,
+        },
+    );
+    registry.register(
+        DOCKERFILE_TYPE_ID,
+        MultipleInstructions {
+            name: "multiple-entrypoint",
+            field_id: COMMAND_ENTRYPOINT,
+        },
+    );
+    registry.register(DOCKERFILE_TYPE_ID, FinalUserRoot);
+    registry.register(COMMAND_TYPE_ID, AddInsteadOfCopy);
+    registry.register(FROM_TYPE_ID, UnpinnedImage);
+    registry.register(FROM_TYPE_ID, LatestTag);
+    registry.register(RUN_NETWORK_TYPE_ID, RunNetworkConflict);
+}
+
+fn has_field(object: &ObjectValue, field_id: ID) -> bool {
+    object.fields.get(&field_id).is_some_and(|v| !v.is_empty())
+}
+
+// The commands of a Dockerfile, with their index in the `commands` field. Missing nodes are
+// skipped.
+fn commands(cx: &LintContext, dockerfile: &ObjectValue) -> Vec<(usize, ObjectValue)> {
+    dockerfile
+        .fields
+        .get(&DOCKERFILE_COMMANDS)
+        .map(|digests| {
+            digests
+                .iter()
+                .enumerate()
+                .filter_map(|(index, digest)| cx.object(digest).map(|command| (index, command)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+struct FirstCommandNotFrom;
+
+impl LintRule for FirstCommandNotFrom {
+    fn name(&self) -> &'static str {
+        "first-command-not-from"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        // Only ARG may come before the first FROM.
+        let first = commands(cx, object)
+            .into_iter()
+            .find(|(_, command)| !has_field(command, COMMAND_ARG));
+        match first {
+            Some((index, command)) if !has_field(&command, COMMAND_FROM) => {
+                vec![Diagnostic::new(
+                    Severity::Error,
+                    &field_path(path, DOCKERFILE_COMMANDS, index),
+                    "the first instruction must be FROM",
+                )]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+// Only the last instruction of a kind in each build stage takes effect.
+struct MultipleInstructions {
+    name: &'static str,
+    field_id: ID,
+}
+
+impl LintRule for MultipleInstructions {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut stage: Vec<usize> = vec![];
+        let mut flush = |stage: &mut Vec<usize>| {
+            if let Some((_, overridden)) = stage.split_last() {
+                for index in overridden {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Warning,
+                        &field_path(path, DOCKERFILE_COMMANDS, *index),
+                        "overridden by a later instruction of the same kind in this stage",
+                    ));
+                }
+            }
+            stage.clear();
+        };
+        for (index, command) in commands(cx, object) {
+            if has_field(&command, COMMAND_FROM) {
+                flush(&mut stage);
+            }
+            if has_field(&command, self.field_id) {
+                stage.push(index);
+            }
+        }
+        flush(&mut stage);
+        diagnostics
+    }
+}
+
+struct FinalUserRoot;
+
+impl LintRule for FinalUserRoot {
+    fn name(&self) -> &'static str {
+        "final-user-root"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        // The user of the image is the last one set in the final stage.
+        let last_user = commands(cx, object)
+            .into_iter()
+            .rev()
+            .take_while(|(_, command)| !has_field(command, COMMAND_FROM))
+            .find_map(|(index, command)| {
+                cx.string(&command, COMMAND_USER).map(|user| (index, user))
+            });
+        match last_user {
+            Some((index, user)) if is_root(&user) => {
+                let command_path = field_path(path, DOCKERFILE_COMMANDS, index);
+                vec![Diagnostic::new(
+                    Severity::Warning,
+                    &field_path(&command_path, COMMAND_USER, 0),
+                    "the image runs as root; switch to an unprivileged user at the end",
+                )]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+// Whether a USER argument, `<user>[:<group>]`, refers to root.
+fn is_root(user: &str) -> bool {
+    let user = user.trim();
+    let name = user.split_once(':').map_or(user, |(name, _)| name);
+    name == "root" || name == "0"
+}
+
+struct AddInsteadOfCopy;
+
+impl LintRule for AddInsteadOfCopy {
+    fn name(&self) -> &'static str {
+        "add-instead-of-copy"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        let Some(digests) = object.fields.get(&COMMAND_ADD).filter(|v| !v.is_empty()) else {
+            return vec![];
+        };
+        let arguments: Vec<String> = cx
+            .values(object, COMMAND_ADD)
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::String(s) => Some(s),
+                _ => None,
+            })
+            .collect();
+        // All but the last argument are sources; flags such as `--chown` are not.
+        let words: Vec<&str> = arguments
+            .iter()
+            .flat_map(|argument| argument.split_whitespace())
+            .filter(|word| !word.starts_with("--"))
+            .collect();
+        let sources = words.split_last().map_or(&[][..], |(_, sources)| sources);
+        if sources.iter().any(|source| needs_add(source)) {
+            return vec![];
+        }
+        // The same arguments, as a COPY instead.
+        let mut copy = object.clone();
+        copy.fields.remove(&COMMAND_ADD);
+        copy.fields.insert(COMMAND_COPY, digests.clone());
+        vec![Diagnostic::new(
+            Severity::Warning,
+            path,
+            "use COPY instead of ADD for local files",
+        )
+        .with_fix(
+            "replace ADD with COPY",
+            Action::Update(path.clone(), Value::Object(copy)),
+        )]
+    }
+}
+
+// Whether an ADD source is a remote URL or a local tar archive, which ADD extracts.
+fn needs_add(source: &str) -> bool {
+    let remote = ["http://", "https://", "git@"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix));
+    let archive = [
+        ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz",
+    ]
+    .iter()
+    .any(|suffix| source.ends_with(suffix));
+    remote || archive
+}
+
+struct UnpinnedImage;
+
+impl LintRule for UnpinnedImage {
+    fn name(&self) -> &'static str {
+        "unpinned-image"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        // `scratch` is the empty image, and has no versions.
+        let image = cx.non_empty_string(object, FROM_IMAGE).unwrap_or_default();
+        if image == "scratch"
+            || cx.non_empty_string(object, FROM_TAG).is_some()
+            || cx.non_empty_string(object, FROM_DIGEST).is_some()
+        {
+            return vec![];
+        }
+        vec![Diagnostic::new(
+            Severity::Warning,
+            path,
+            format!("image {:?} is not pinned to a tag or digest", image),
+        )]
+    }
+}
+
+struct LatestTag;
+
+impl LintRule for LatestTag {
+    fn name(&self) -> &'static str {
+        "latest-tag"
+    }
+
+    fn check(
+        &self,
+        cx: &LintContext,
+        _object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        match cx.string(object, FROM_TAG) {
+            Some(tag) if tag.trim() == "latest" => vec![Diagnostic::new(
+                Severity::Warning,
+                &field_path(path, FROM_TAG, 0),
+                "the latest tag changes over time; pin a specific version",
+            )],
+            _ => vec![],
+        }
+    }
+}
+
+struct RunNetworkConflict;
+
+impl LintRule for RunNetworkConflict {
+    fn name(&self) -> &'static str {
+        "run-network-conflict"
+    }
+
+    fn check(
+        &self,
+        _cx: &LintContext,
+        object_type: &ObjectType,
+        object: &ObjectValue,
+        path: &Path,
+    ) -> Vec<Diagnostic> {
+        let modes: Vec<String> = [RUN_NETWORK_DEFAULT, RUN_NETWORK_NONE, RUN_NETWORK_HOST]
+            .into_iter()
+            .filter(|field_id| has_field(object, *field_id))
+            .filter_map(|field_id| object_type.fields.get(&field_id))
+            .map(|field_type| field_type.name.clone())
+            .collect();
+        if modes.len() > 1 {
+            vec![Diagnostic::new(
+                Severity::Error,
+                path,
+                format!("conflicting network modes: {}", modes.join(", ")),
+            )]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    create_schema, test_object_node, test_string_node, MemoryStore, Selector, Store, COMMAND_RUN,
+    D, RUN_NETWORK, RUN_TYPE_ID,
+};
+#[cfg(test)]
+use maplit::btreemap;
+
+#[test]
+fn test_needs_add() {
+    assert!(needs_add("https://example.com/file"));
+    assert!(needs_add("rootfs.tar.gz"));
+    assert!(!needs_add("src/"));
+    assert!(!needs_add("Cargo.toml"));
+}
+
+#[test]
+fn test_dockerfile_rules() {
+    let store = MemoryStore::new();
+    // A command with a single instruction, with the given string arguments.
+    let single_command = |field_id: ID, arguments: &[&str]| {
+        let arguments = arguments
+            .iter()
+            .map(|argument| store.put(test_string_node(0, argument)))
+            .collect();
+        store.put(test_object_node(
+            0,
+            COMMAND_TYPE_ID,
+            btreemap! { field_id => arguments },
+        ))
+    };
+    let image = store.put(test_string_node(0, "rust"));
+    let latest = store.put(test_string_node(0, "latest"));
+    let unpinned = store.put(test_object_node(
+        0,
+        FROM_TYPE_ID,
+        btreemap! { FROM_IMAGE => vec![image.clone()] },
+    ));
+    let latest = store.put(test_object_node(
+        0,
+        FROM_TYPE_ID,
+        btreemap! { FROM_IMAGE => vec![image], FROM_TAG => vec![latest] },
+    ));
+    let empty = store.put(test_string_node(0, ""));
+    let network = store.put(test_object_node(
+        0,
+        RUN_NETWORK_TYPE_ID,
+        btreemap! {
+            RUN_NETWORK_NONE => vec![empty.clone()],
+            RUN_NETWORK_HOST => vec![empty],
+        },
+    ));
+    let run = store.put(test_object_node(
+        0,
+        RUN_TYPE_ID,
+        btreemap! { RUN_NETWORK => vec![network] },
+    ));
+    let add_arguments = vec![
+        store.put(test_string_node(0, "src/")),
+        store.put(test_string_node(0, "/app")),
+    ];
+    let add = store.put(test_object_node(
+        0,
+        COMMAND_TYPE_ID,
+        btreemap! { COMMAND_ADD => add_arguments.clone() },
+    ));
+    let commands = vec![
+        single_command(COMMAND_ARG, &["VERSION"]),
+        single_command(COMMAND_COPY, &["a", "b"]),
+        store.put(test_object_node(
+            0,
+            COMMAND_TYPE_ID,
+            btreemap! { COMMAND_FROM => vec![unpinned] },
+        )),
+        single_command(COMMAND_CMD, &["a"]),
+        single_command(COMMAND_CMD, &["b"]),
+        add,
+        store.put(test_object_node(
+            0,
+            COMMAND_TYPE_ID,
+            btreemap! { COMMAND_RUN => vec![run] },
+        )),
+        store.put(test_object_node(
+            0,
+            COMMAND_TYPE_ID,
+            btreemap! { COMMAND_FROM => vec![latest] },
+        )),
+        single_command(COMMAND_CMD, &["c"]),
+        single_command(COMMAND_USER, &["root"]),
+        single_command(COMMAND_ADD, &["https://example.com/file", "/app"]),
+    ];
+    let dockerfile = store.put(test_object_node(
+        0,
+        DOCKERFILE_TYPE_ID,
+        btreemap! { DOCKERFILE_COMMANDS => commands },
+    ));
+    let get_node = |d: &D| store.get(d);
+    let diagnostics = super::default_lint_registry().run(&create_schema(), &get_node, &dockerfile);
+
+    let command = |index| {
+        vec![Selector {
+            field_id: DOCKERFILE_COMMANDS,
+            index,
+        }]
+    };
+    let summary: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.rule, diagnostic.path.clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("first-command-not-from", command(1)),
+            ("multiple-cmd", command(3)),
+            ("final-user-root", field_path(&command(9), COMMAND_USER, 0)),
+            ("unpinned-image", field_path(&command(2), COMMAND_FROM, 0)),
+            ("add-instead-of-copy", command(5)),
+            (
+                "run-network-conflict",
+                field_path(&field_path(&command(6), COMMAND_RUN, 0), RUN_NETWORK, 0)
+            ),
+            (
+                "latest-tag",
+                field_path(&field_path(&command(7), COMMAND_FROM, 0), FROM_TAG, 0)
+            ),
+        ]
+    );
+    assert_eq!(
+        diagnostics[4].fix.as_ref().map(|fix| &fix.action),
+        Some(&Action::Update(
+            command(5),
+            Value::Object(ObjectValue {
+                object_type_id: COMMAND_TYPE_ID,
+                fields: btreemap! { COMMAND_COPY => add_arguments },
+            })
+        ))
+    );
+}
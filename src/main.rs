@@ -7,18 +7,43 @@ use maplit::btreemap;
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Display, Formatter},
-    sync::Arc,
 };
 
+mod article;
+mod bundle;
+mod cargo_manifest;
+mod dockerfile;
+mod gc;
 mod schema;
 mod storage;
+mod validation;
 mod components;
+mod history;
+mod json;
+mod keymap;
+mod lint;
+mod markdown;
+mod merge;
+mod refs;
 
+use article::*;
+use bundle::*;
+use cargo_manifest::*;
+use dockerfile::*;
+use gc::*;
 use schema::*;
 use storage::*;
+use validation::*;
 use components::*;
+use history::*;
+use json::*;
+use keymap::*;
+use lint::*;
+use markdown::*;
+use merge::*;
+use refs::*;
 
 const ESCAPE_KEY: u32 = 27;
 const ENTER_KEY: u32 = 13;
@@ -38,7 +63,7 @@ fn format_path(p: &Path) -> String {
         .join(".")
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct Schema {
     root_object_type_id: ID,
     object_types: HashMap<ID, ObjectType>,
@@ -50,7 +75,7 @@ fn new_id() -> ID {
     rand::random()
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum Type {
     String,
     Int,
@@ -64,6 +89,10 @@ enum Type {
 struct Node {
     id: ID,
     value: Value,
+    // Digest of the schema the node was authored against, usually only set on the root of a
+    // document. Omitted when empty, so that it does not affect the digest of other nodes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schema: Option<D>,
 }
 
 impl Node {
@@ -73,6 +102,14 @@ impl Node {
     fn deserialize(s: &str) -> Option<Node> {
         serde_json::from_str(s).ok()
     }
+    // Digests of the nodes this node refers to: its schema, then the entries of its fields.
+    fn children(&self) -> Vec<D> {
+        let mut children = self.schema.iter().cloned().collect::<Vec<_>>();
+        if let Value::Object(object) = &self.value {
+            children.extend(object.fields.values().flatten().cloned());
+        }
+        children
+    }
 }
 
 impl Type {
@@ -88,25 +125,27 @@ impl Type {
             }),
         }
     }
+}
 
-    fn is_primitive(&self) -> bool {
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Type::String => true,
-            Type::Int => true,
-            Type::Number => true,
-            Type::Boolean => true,
-            Type::Object(_) => false,
+            Type::String => write!(f, "string"),
+            Type::Int => write!(f, "integer"),
+            Type::Number => write!(f, "number"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Object(object_type_id) => write!(f, "object #{}", object_type_id),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct ObjectType {
     name: String,
     fields: BTreeMap<ID, FieldType>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct FieldType {
     name: String,
     type_: Type,
@@ -148,6 +187,43 @@ impl Value {
             Type::Object(_) => None,
         }
     }
+
+    // Type the value is an instance of, if any.
+    fn type_(&self) -> Option<Type> {
+        match self {
+            Value::Empty => None,
+            Value::String(_) => Some(Type::String),
+            Value::Int(_) => Some(Type::Int),
+            Value::Number(_) => Some(Type::Number),
+            Value::Boolean(_) => Some(Type::Boolean),
+            Value::Object(object) => Some(Type::Object(object.object_type_id)),
+        }
+    }
+
+    // Text representation of a primitive value when editing it, which `parse` turns back into
+    // the same value.
+    fn edit_text(&self) -> String {
+        match self {
+            Value::Empty => String::new(),
+            v => v.to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_value_parse_edit_text() {
+    for (type_, value) in [
+        (Type::String, Value::String("hello".to_string())),
+        (Type::Int, Value::Int(-42)),
+        (Type::Number, Value::Number(1.5)),
+        (Type::Boolean, Value::Boolean(true)),
+    ] {
+        assert_eq!(value.type_(), Some(type_.clone()));
+        assert_eq!(Value::parse(type_, &value.edit_text()), Some(value));
+    }
+    assert_eq!(Value::parse(Type::Int, "1.5"), None);
+    assert_eq!(Value::parse(Type::Number, "abc"), None);
+    assert_eq!(Value::parse(Type::Boolean, "yes"), None);
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -157,9 +233,23 @@ struct ObjectValue {
 }
 
 impl ObjectValue {
-    fn append(&mut self, field_id: ID, value: D) {
+    fn insert(&mut self, field_id: ID, position: Position, value: D) {
         let field = self.fields.entry(field_id).or_default();
-        field.push(value);
+        let index = position.index(field.len());
+        field.insert(index, value);
+    }
+    // Move the entry at the given index to the given position, expressed in terms of the indices
+    // before the move. Returns the new index of the entry.
+    fn move_to(&mut self, field_id: ID, index: usize, position: Position) -> Option<usize> {
+        let field = self.fields.get_mut(&field_id)?;
+        if index >= field.len() {
+            return None;
+        }
+        let target = position.index(field.len());
+        let value = field.remove(index);
+        let target = if target > index { target - 1 } else { target };
+        field.insert(target, value);
+        Some(target)
     }
     fn delete(&mut self, field_id: ID, index: usize) {
         self.fields.get_mut(&field_id).map(|v| v.remove(index));
@@ -177,7 +267,14 @@ type FieldValue = RwSignal<Vec<RwSignal<Value>>>;
 
 fn main() {
     console_error_panic_hook::set_once();
-    mount_to_body(|| view! { <App/> })
+    // Nodes are read synchronously while rendering, so the store has to be open first.
+    spawn_local(async {
+        let store = NodeStore::new(open_backend().await);
+        mount_to_body(move || {
+            provide_context(store);
+            view! { <App/> }
+        })
+    });
 }
 
 fn single_field_value(value: Value) -> FieldValue {
@@ -195,149 +292,302 @@ fn create_value() -> Value {
     })
 }
 
-#[allow(unused)]
-fn parent(schema: &Schema, root_value: &Value, path: &Path) -> Path {
+fn parent(path: &Path) -> Path {
     let mut path = path.clone();
     path.pop();
     path
 }
 
-// Traverse the value to find the child at the given path.
-#[allow(unused)]
-fn child(schema: &Schema, root_value: &Value, path: &Path) -> Path {
-      todo!()
-    // let value = find_value(root_value, path).unwrap();
-    // let mut path = path.clone();
-    // match value {
-    //     Value::Object(o) => {
-    //         let object_type = schema.object_types.get(&o.object_type_id).unwrap();
-    //         for (field_id, field_type) in object_type.fields.iter() {
-    //             let field = o.fields.get(field_id).unwrap();
-    //             if field_type.type_.is_primitive() {
-    //                 path.push(Selector {
-    //                     field_id: *field_id,
-    //                     index: 0,
-    //                 });
-    //                 break;
-    //             }
-    //         }
-    //     }
-    //     _ => {}
-    // }
-    // path
-}
-
-#[allow(unused)]
-fn prev(schema: &Schema, root_value: &Value, path: &Path) -> Path {
-    // TODO
-    return path.clone();
-}
-
-#[allow(unused)]
-fn next(schema: &Schema, root_value: &Value, path: &Path) -> Path {
-    todo!()
-    // let starting_ancestor = match find_value(root_value, path).unwrap() {
-    //     Value::Object(_) => Some(path.clone()),
-    //     _ => ancestor_with_next_child(schema, root_value, path),
-    // };
-    // logging::log!("starting_ancestor {:?}", starting_ancestor);
-    // match starting_ancestor {
-    //     Some(ancestor) => first_leaf(schema, root_value, &ancestor)
-    //         .get_or_insert(path.clone())
-    //         .to_vec(),
-    //     None => path.clone(),
-    // }
-}
-
-#[allow(unused)]
-fn ancestor_with_next_child(schema: &Schema, root_value: &Value, path: &Path) -> Option<Path> {
-    todo!()
-    // let parent_path = parent(schema, root_value, path);
-    // let parent_value = find_value(root_value, &parent_path).unwrap();
-    // match parent_value {
-    //     Value::Object(parent_object) => {
-    //         let mut parent_type_fields = schema.object_types[&parent_object.object_type_id]
-    //             .fields
-    //             .clone();
-    //         let current_path_field_id = match path.last() {
-    //             Some(selector) => selector.field_id,
-    //             None => panic!("no values"),
-    //         };
-    //         let subsequent_parent_type_fields =
-    //             parent_type_fields.split_off(&current_path_field_id);
-    //         let mut iter = subsequent_parent_type_fields.iter();
-    //         let (current_field_id, current_field_type) = iter.next().unwrap();
-    //         if current_field_type.repeated {
-    //             let field_entries_size = parent_object.fields[current_field_id].len();
-    //             let current_field_index = path.last().unwrap().index;
-    //             if current_field_index < field_entries_size - 1 {
-    //                 let mut next_leaf_path = parent_path.clone();
-    //                 next_leaf_path.push(Selector {
-    //                     field_id: *current_field_id,
-    //                     index: current_field_index + 1,
-    //                 });
-    //                 return Some(next_leaf_path);
-    //             }
-    //         }
-    //         for (field_id, field_type) in iter {
-    //             if parent_object.fields.contains_key(field_id) {
-    //                 let mut next_leaf_path = parent_path.clone();
-    //                 next_leaf_path.push(Selector {
-    //                     field_id: *field_id,
-    //                     index: 0,
-    //                 });
-    //                 return Some(next_leaf_path);
-    //             }
-    //         }
-    //     }
-    //     _ => panic!("not an object"),
-    // }
-    // if parent_path.len() == 0 {
-    //     return None;
-    // }
-    // return ancestor_with_next_child(schema, root_value, &parent_path);
-}
-
-fn first_leaf(schema: &Schema, root_digest: &D, path: &Path) -> Option<Path> {
-    let digest = find_value(root_digest, path).unwrap();
-    let value = get_item(&digest).get_untracked().unwrap().value.clone();
-    let mut first_leaf_path = path.clone();
-    match value {
-        Value::Object(object_value) => {
-            let type_fields = schema.object_types[&object_value.object_type_id]
-                .fields
-                .clone();
-            match type_fields.first_key_value() {
-                Some((field_id, _field_type)) => {
-                    first_leaf_path.push(Selector {
-                        field_id: *field_id,
-                        index: 0,
-                    });
-                    first_leaf(schema, root_digest, &first_leaf_path)
-                }
-                None => panic!("no values"),
-            }
+// Selectors of the direct children of the given node, in document order: fields in field id
+// order, and within each field its entries in index order.
+fn child_selectors<G: Fn(&D) -> Option<Node>>(get_node: &G, digest: &D) -> Vec<Selector> {
+    match get_node(digest).map(|node| node.value) {
+        Some(Value::Object(object)) => object
+            .fields
+            .iter()
+            .flat_map(|(field_id, field)| {
+                (0..field.len()).map(move |index| Selector {
+                    field_id: *field_id,
+                    index,
+                })
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn child_selectors_at<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    root_digest: &D,
+    path: &Path,
+) -> Vec<Selector> {
+    find_value_with(get_node, root_digest, path)
+        .map(|digest| child_selectors(get_node, &digest))
+        .unwrap_or_default()
+}
+
+// Traverse the value to find the first child of the node at the given path. Returns the path
+// itself if the node has no children.
+fn child<G: Fn(&D) -> Option<Node>>(get_node: &G, root_digest: &D, path: &Path) -> Path {
+    let mut path = path.clone();
+    if let Some(selector) = child_selectors_at(get_node, root_digest, &path).first() {
+        path.push(selector.clone());
+    }
+    path
+}
+
+fn next_sibling<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    root_digest: &D,
+    path: &Path,
+) -> Option<Path> {
+    let selector = path.last()?;
+    let mut parent_path = parent(path);
+    let siblings = child_selectors_at(get_node, root_digest, &parent_path);
+    let position = siblings.iter().position(|s| s == selector)?;
+    parent_path.push(siblings.get(position + 1)?.clone());
+    Some(parent_path)
+}
+
+fn prev_sibling<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    root_digest: &D,
+    path: &Path,
+) -> Option<Path> {
+    let selector = path.last()?;
+    let mut parent_path = parent(path);
+    let siblings = child_selectors_at(get_node, root_digest, &parent_path);
+    let position = siblings.iter().position(|s| s == selector)?;
+    parent_path.push(siblings.get(position.checked_sub(1)?)?.clone());
+    Some(parent_path)
+}
+
+// Follow the last child of each node until reaching a node without children.
+fn last_descendant<G: Fn(&D) -> Option<Node>>(get_node: &G, root_digest: &D, path: &Path) -> Path {
+    let mut path = path.clone();
+    while let Some(selector) = child_selectors_at(get_node, root_digest, &path).pop() {
+        path.push(selector);
+    }
+    path
+}
+
+// Previous node in document order: the deepest last descendant of the previous sibling, or the
+// parent if this is the first child.
+fn prev<G: Fn(&D) -> Option<Node>>(get_node: &G, root_digest: &D, path: &Path) -> Path {
+    if path.is_empty() {
+        return path.clone();
+    }
+    match prev_sibling(get_node, root_digest, path) {
+        Some(sibling_path) => last_descendant(get_node, root_digest, &sibling_path),
+        None => parent(path),
+    }
+}
+
+// Next node in document order: the first child if there is one, otherwise the next sibling of
+// the node or of its closest ancestor that has one. Returns the path itself at the end of the
+// document.
+fn next<G: Fn(&D) -> Option<Node>>(get_node: &G, root_digest: &D, path: &Path) -> Path {
+    let first_child = child(get_node, root_digest, path);
+    if first_child.len() > path.len() {
+        return first_child;
+    }
+    ancestor_with_next_child(get_node, root_digest, path).unwrap_or_else(|| path.clone())
+}
+
+// Walk up from the given path and return the path of the next sibling of the first node (starting
+// from the node itself) that is not the last child of its parent.
+fn ancestor_with_next_child<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    root_digest: &D,
+    path: &Path,
+) -> Option<Path> {
+    let mut path = path.clone();
+    while !path.is_empty() {
+        if let Some(sibling_path) = next_sibling(get_node, root_digest, &path) {
+            return Some(sibling_path);
         }
-        _ => Some(first_leaf_path),
+        path.pop();
     }
+    None
 }
 
-fn find_value(root_digest: &D, path: &Path) -> Option<D> {
+fn find_value<S: Store<Node, D>>(store: &S, root_digest: &D, path: &Path) -> Option<D> {
     logging::log!("find_value {:?} {:?}", root_digest.to_hex(), path);
-    if path.is_empty() {
-        return Some(root_digest.clone());
-    } else {
-        let node = get_item(root_digest).get_untracked().unwrap();
-        let object = match node.value {
-            Value::Object(v) => v,
-            _ => panic!("expected object value"),
-        };
-        let selector = path.first().unwrap();
-        let field = object.fields.get(&selector.field_id).unwrap();
-        let next_path = path[1..].to_vec();
-        let next_digest = field.get(selector.index).unwrap();
-        return find_value(&next_digest, &next_path);
+    find_value_with(&|digest: &D| store.get(digest), root_digest, path)
+}
+
+fn find_value_with<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    root_digest: &D,
+    path: &Path,
+) -> Option<D> {
+    match path.split_first() {
+        None => Some(root_digest.clone()),
+        Some((selector, next_path)) => {
+            let object = match get_node(root_digest)?.value {
+                Value::Object(v) => v,
+                _ => return None,
+            };
+            let next_digest = object.fields.get(&selector.field_id)?.get(selector.index)?;
+            find_value_with(get_node, next_digest, &next_path.to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_node_map(nodes: &[Node]) -> HashMap<D, Node> {
+    nodes
+        .iter()
+        .map(|node| (node.digest(), node.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+fn test_node(id: ID, value: Value) -> Node {
+    Node {
+        id,
+        value,
+        schema: None,
+    }
+}
+
+#[cfg(test)]
+fn test_string_node(id: ID, s: &str) -> Node {
+    test_node(id, Value::String(s.to_string()))
+}
+
+#[cfg(test)]
+fn test_object_node(id: ID, object_type_id: ID, fields: BTreeMap<ID, Vec<D>>) -> Node {
+    test_node(
+        id,
+        Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        }),
+    )
+}
+
+#[cfg(test)]
+fn sel(field_id: ID, index: usize) -> Selector {
+    Selector { field_id, index }
+}
+
+// Builds the following graph, listed in document order:
+//
+// root
+//   0[0]: a
+//   1[0]: inner
+//     0[0]: c
+//     0[1]: d
+//   1[1]: b
+//   2: (empty field)
+#[cfg(test)]
+fn test_navigation_graph() -> (D, HashMap<D, Node>) {
+    let a = test_string_node(1, "a");
+    let b = test_string_node(2, "b");
+    let c = test_string_node(3, "c");
+    let d = test_string_node(4, "d");
+    let inner = test_object_node(
+        5,
+        0,
+        btreemap! {
+            0 => vec![c.digest(), d.digest()],
+        },
+    );
+    let root = test_object_node(
+        6,
+        0,
+        btreemap! {
+            0 => vec![a.digest()],
+            1 => vec![inner.digest(), b.digest()],
+            2 => vec![],
+        },
+    );
+    let root_digest = root.digest();
+    (root_digest, test_node_map(&[a, b, c, d, inner, root]))
+}
+
+#[test]
+fn test_find_value_with() {
+    let (root, nodes) = test_navigation_graph();
+    let get_node = |d: &D| nodes.get(d).cloned();
+    let d = find_value_with(&get_node, &root, &vec![sel(1, 0), sel(0, 1)]).unwrap();
+    assert_eq!(nodes[&d].value, Value::String("d".to_string()));
+    assert_eq!(find_value_with(&get_node, &root, &vec![sel(1, 2)]), None);
+    assert_eq!(
+        find_value_with(&get_node, &root, &vec![sel(0, 0), sel(0, 0)]),
+        None
+    );
+}
+
+#[test]
+fn test_parent_and_child() {
+    let (root, nodes) = test_navigation_graph();
+    let get_node = |d: &D| nodes.get(d).cloned();
+    assert_eq!(child(&get_node, &root, &vec![]), vec![sel(0, 0)]);
+    assert_eq!(
+        child(&get_node, &root, &vec![sel(1, 0)]),
+        vec![sel(1, 0), sel(0, 0)]
+    );
+    // Leaves have no children.
+    assert_eq!(child(&get_node, &root, &vec![sel(0, 0)]), vec![sel(0, 0)]);
+    assert_eq!(parent(&vec![sel(1, 0), sel(0, 1)]), vec![sel(1, 0)]);
+    assert_eq!(parent(&vec![]), Path::new());
+}
+
+#[test]
+fn test_next_and_prev_follow_document_order() {
+    let (root, nodes) = test_navigation_graph();
+    let get_node = |d: &D| nodes.get(d).cloned();
+    let expected = [
+        vec![],
+        vec![sel(0, 0)],
+        vec![sel(1, 0)],
+        vec![sel(1, 0), sel(0, 0)],
+        vec![sel(1, 0), sel(0, 1)],
+        vec![sel(1, 1)],
+    ];
+    for pair in expected.windows(2) {
+        assert_eq!(next(&get_node, &root, &pair[0]), pair[1]);
+        assert_eq!(prev(&get_node, &root, &pair[1]), pair[0]);
     }
+    // Both ends of the document are fixed points.
+    assert_eq!(next(&get_node, &root, &vec![sel(1, 1)]), vec![sel(1, 1)]);
+    assert_eq!(prev(&get_node, &root, &vec![]), Path::new());
+}
+
+#[test]
+fn test_ancestor_with_next_child() {
+    let (root, nodes) = test_navigation_graph();
+    let get_node = |d: &D| nodes.get(d).cloned();
+    assert_eq!(
+        ancestor_with_next_child(&get_node, &root, &vec![sel(1, 0), sel(0, 1)]),
+        Some(vec![sel(1, 1)])
+    );
+    assert_eq!(
+        ancestor_with_next_child(&get_node, &root, &vec![sel(0, 0)]),
+        Some(vec![sel(1, 0)])
+    );
+    assert_eq!(
+        ancestor_with_next_child(&get_node, &root, &vec![sel(1, 1)]),
+        None
+    );
+    assert_eq!(ancestor_with_next_child(&get_node, &root, &vec![]), None);
+}
+
+// The result of importing a document from another format.
+trait Imported {
+    fn root(&self) -> &D;
+    // Feedback about the import, e.g. parts of the source that were left out.
+    fn messages(&self) -> Vec<String>;
+}
+
+// Messages for the parts of a source that were left out of an import.
+fn not_imported<T: Display>(parts: &[T]) -> Vec<String> {
+    parts
+        .iter()
+        .map(|part| format!("not imported: {}", part))
+        .collect()
 }
 
 trait HasDigest {
@@ -365,11 +615,64 @@ impl D {
         }
     }
 
+    // Like `from_hex`, but for input that may not be a valid digest.
+    pub fn parse_hex(s: &str) -> Option<Self> {
+        let sha2_256 = hex::decode(s.strip_prefix("sha2-256:")?).ok()?;
+        Some(D {
+            sha2_256: sha2_256.try_into().ok()?,
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.sha2_256.iter().all(|&b| b == 0)
     }
 }
 
+#[cfg(test)]
+fn test_object_value(entries: Vec<D>) -> ObjectValue {
+    ObjectValue {
+        object_type_id: 0,
+        fields: btreemap! {
+            0 => entries,
+        },
+    }
+}
+
+#[cfg(test)]
+fn test_digest(n: u8) -> D {
+    D { sha2_256: [n; 32] }
+}
+
+#[test]
+fn test_object_value_insert() {
+    let mut object = test_object_value([1, 2].map(test_digest).to_vec());
+    object.insert(0, Position::First, test_digest(0));
+    object.insert(0, Position::Last, test_digest(9));
+    object.insert(0, Position::Before(2), test_digest(5));
+    object.insert(0, Position::After(0), test_digest(6));
+    assert_eq!(object.fields[&0], [0, 6, 1, 5, 2, 9].map(test_digest));
+    // Out of range positions are clamped, missing fields are created.
+    object.insert(0, Position::After(100), test_digest(7));
+    object.insert(1, Position::Before(3), test_digest(8));
+    assert_eq!(object.fields[&0], [0, 6, 1, 5, 2, 9, 7].map(test_digest));
+    assert_eq!(object.fields[&1], [8].map(test_digest));
+}
+
+#[test]
+fn test_object_value_move_to() {
+    let mut object = test_object_value([0, 1, 2, 3].map(test_digest).to_vec());
+    assert_eq!(object.move_to(0, 0, Position::After(1)), Some(1));
+    assert_eq!(object.fields[&0], [1, 0, 2, 3].map(test_digest));
+    assert_eq!(object.move_to(0, 3, Position::First), Some(0));
+    assert_eq!(object.fields[&0], [3, 1, 0, 2].map(test_digest));
+    assert_eq!(object.move_to(0, 1, Position::Last), Some(3));
+    assert_eq!(object.fields[&0], [3, 0, 2, 1].map(test_digest));
+    assert_eq!(object.move_to(0, 2, Position::Before(1)), Some(1));
+    assert_eq!(object.fields[&0], [3, 2, 0, 1].map(test_digest));
+    assert_eq!(object.move_to(0, 4, Position::First), None);
+    assert_eq!(object.move_to(1, 0, Position::First), None);
+}
+
 // test for from_hex
 #[test]
 fn test_d_from_hex() {
@@ -388,7 +691,7 @@ impl HasDigest for Node {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Position {
     First,
     Last,
@@ -396,7 +699,20 @@ enum Position {
     After(usize),
 }
 
-#[derive(Clone, Debug)]
+impl Position {
+    // Index at which to insert in a field with `len` entries.
+    fn index(&self, len: usize) -> usize {
+        let index = match self {
+            Position::First => 0,
+            Position::Last => len,
+            Position::Before(index) => *index,
+            Position::After(index) => index + 1,
+        };
+        index.min(len)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 enum Action {
     Noop,
     Update(Path, Value),
@@ -410,28 +726,33 @@ enum Action {
     Delete {
         path: Path,
     },
+    // Path of the entry to move within its field.
+    Move {
+        path: Path,
+        position: Position,
+    },
     SetFocus {
         path: Path,
     },
 }
 
-#[derive(Default)]
-struct State {
-    history: Vec<D>,
+fn update_node<S: Store<Node, D>>(store: &S, base: &D, path: &Path, value: Value) -> D {
+    update_node_value(store, base, path, |_| value)
 }
 
-fn update_node(base: &D, path: &Path, value: Value) -> D {
-    update_node_value(base, path, |_| value)
-}
-
-fn update_node_value<F: FnOnce(Value) -> Value>(base: &D, path: &Path, update_fn: F) -> D {
+fn update_node_value<S: Store<Node, D>, F: FnOnce(Value) -> Value>(
+    store: &S,
+    base: &D,
+    path: &Path,
+    update_fn: F,
+) -> D {
     logging::log!("update_node {:?} -> (fn)", path);
     if path.is_empty() {
-        let mut node = get_item(base).get_untracked().unwrap();
+        let mut node = store.get(base).unwrap();
         node.value = update_fn(node.value);
-        set_item(&node)
+        store.put(node)
     } else {
-        let mut node = get_item(base).get_untracked().unwrap();
+        let mut node = store.get(base).unwrap();
         logging::log!("node {:?}", node);
         let mut object = match node.value {
             Value::Object(v) => v,
@@ -441,13 +762,52 @@ fn update_node_value<F: FnOnce(Value) -> Value>(base: &D, path: &Path, update_fn
         let selector = path.first().unwrap();
         logging::log!("selector {:?}", selector);
         let child = object.get(selector.field_id, selector.index).unwrap();
-        let new_next_digest = update_node_value(child, &path[1..].to_vec(), update_fn);
+        let new_next_digest = update_node_value(store, child, &path[1..].to_vec(), update_fn);
         object.set(selector.field_id, selector.index, new_next_digest);
         node.value = Value::Object(object);
-        set_item(&node)
+        store.put(node)
     }
 }
 
+// Returns the digest of the node with its schema reference replaced.
+fn update_node_schema<S: Store<Node, D>>(store: &S, base: &D, schema: D) -> D {
+    let mut node = store.get(base).unwrap();
+    node.schema = Some(schema);
+    store.put(node)
+}
+
+#[cfg(test)]
+fn test_store(nodes: HashMap<D, Node>) -> MemoryStore<Node, D> {
+    let store = MemoryStore::new();
+    for node in nodes.into_values() {
+        Store::put(&store, node);
+    }
+    store
+}
+
+#[test]
+fn test_update_node() {
+    let (root, nodes) = test_navigation_graph();
+    let store = test_store(nodes);
+    let path = vec![sel(1, 0), sel(0, 1)];
+    let new_root = update_node(&store, &root, &path, Value::String("e".to_string()));
+    let value_at = |root: &D, path: &Path| {
+        find_value(&store, root, path)
+            .and_then(|digest| Store::get(&store, &digest))
+            .map(|node| node.value)
+    };
+    assert_eq!(
+        value_at(&new_root, &path),
+        Some(Value::String("e".to_string()))
+    );
+    // The previous version is still there, and shares the nodes that did not change.
+    assert_eq!(value_at(&root, &path), Some(Value::String("d".to_string())));
+    assert_eq!(
+        find_value(&store, &new_root, &vec![sel(1, 1)]),
+        find_value(&store, &root, &vec![sel(1, 1)])
+    );
+}
+
 #[component]
 fn List() -> impl IntoView {
     let (v, set_v) = create_signal(vec![1, 2, 3]);
@@ -518,8 +878,12 @@ async fn upload_node(api_key: &str, node: Node) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn upload(api_key: String, digest: D) -> leptos::error::Result<()> {
-    traverse_async(digest, move |node: Node| {
+async fn upload<S: AsyncStore<Node, D>>(
+    store: &S,
+    api_key: String,
+    digest: D,
+) -> anyhow::Result<()> {
+    traverse_async(store, digest, move |node: Node| {
         let api_key = api_key.clone();
       async move { 
         upload_node(&api_key.clone(), node.clone()).await.unwrap();
@@ -530,34 +894,163 @@ async fn upload(api_key: String, digest: D) -> leptos::error::Result<()> {
 
 const STATIC_SPACE_API_URL: &str = "https://api.static.space";
 
-async fn download(digest: D) -> anyhow::Result<()> {
+#[derive(Clone, Debug, PartialEq)]
+pub enum DownloadError {
+    Request(String),
+    Status { digest: D, status: u16 },
+    Malformed { digest: D },
+    // The content returned for a digest hashes to a different one.
+    DigestMismatch { expected: D, actual: D },
+    // The node was downloaded but could not be written to the local store.
+    Store(String),
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Request(err) => write!(f, "request failed: {}", err),
+            DownloadError::Status { digest, status } => {
+                write!(
+                    f,
+                    "failed to download {}: status {}",
+                    digest.to_hex(),
+                    status
+                )
+            }
+            DownloadError::Malformed { digest } => {
+                write!(f, "content of {} is not a node", digest.to_hex())
+            }
+            DownloadError::DigestMismatch { expected, actual } => write!(
+                f,
+                "integrity error: requested {}, received content with digest {}",
+                expected.to_hex(),
+                actual.to_hex()
+            ),
+            DownloadError::Store(err) => write!(f, "failed to store node: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+// Parses the content downloaded for the given digest, checking that it hashes to that digest.
+fn verify_node(digest: &D, body: &str) -> Result<Node, DownloadError> {
+    let node = Node::deserialize(body).ok_or_else(|| DownloadError::Malformed {
+        digest: digest.clone(),
+    })?;
+    let actual = node.digest();
+    if actual != *digest {
+        return Err(DownloadError::DigestMismatch {
+            expected: digest.clone(),
+            actual,
+        });
+    }
+    Ok(node)
+}
+
+// Downloads and stores the node with the given digest, using `fetch` to obtain its content.
+// Content that does not match the digest is never stored.
+async fn download<S, F>(store: &S, fetch: &F, digest: D) -> Result<(), DownloadError>
+where
+    S: AsyncStore<Node, D>,
+    F: std::ops::AsyncFn(D) -> Result<String, DownloadError>,
+{
     logging::log!("downloading {:?}", digest.to_hex());
+    let body = fetch(digest.clone()).await?;
+    let node = verify_node(&digest, &body)?;
+    logging::log!("node {:?}", node);
+    store
+        .put(node)
+        .await
+        .map_err(|err| DownloadError::Store(err.to_string()))?;
+    Ok(())
+}
+
+// Fetches the content of the node with the given digest from static.space.
+async fn fetch_node(digest: D) -> Result<String, DownloadError> {
     let digest_hex = digest.to_hex();
     let res = reqwasm::http::Request::get(&format!("{STATIC_SPACE_API_URL}/v1/raw/{digest_hex}"))
         .send()
-        .await?;
+        .await
+        .map_err(|err| DownloadError::Request(err.to_string()))?;
     logging::log!("download res {:?}", res);
     if res.status() != 200 {
-        return Err(anyhow::anyhow!("failed to download").into());
+        return Err(DownloadError::Status {
+            digest,
+            status: res.status(),
+        });
+    }
+    res.text()
+        .await
+        .map_err(|err| DownloadError::Request(err.to_string()))
+}
+
+#[test]
+fn test_verify_node() {
+    let node = Node {
+        id: 1,
+        value: Value::String("hello".to_string()),
+        schema: None,
+    };
+    let digest = node.digest();
+    assert_eq!(verify_node(&digest, &node.serialize()), Ok(node.clone()));
+
+    let tampered = Node {
+        value: Value::String("goodbye".to_string()),
+        ..node
+    };
+    assert_eq!(
+        verify_node(&digest, &tampered.serialize()),
+        Err(DownloadError::DigestMismatch {
+            expected: digest.clone(),
+            actual: tampered.digest(),
+        })
+    );
+    assert_eq!(
+        verify_node(&digest, "<html>"),
+        Err(DownloadError::Malformed { digest })
+    );
+}
+
+// Download the node and all its descendants that are not available locally.
+async fn download_missing<S, F>(store: &S, fetch: &F, digest: D) -> anyhow::Result<()>
+where
+    S: AsyncStore<Node, D>,
+    F: std::ops::AsyncFn(D) -> Result<String, DownloadError>,
+{
+    if !store.has(&digest).await? {
+        download(store, fetch, digest.clone()).await?;
+    }
+    let node = store
+        .get(&digest)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("node {} not found", digest.to_hex()))?;
+    if let Value::Object(object) = node.value {
+        for child_digest in object.fields.values().flatten() {
+            Box::pin(download_missing(store, fetch, child_digest.clone())).await?;
+        }
     }
-    let body = res.text().await?;
-    // parse node
-    logging::log!("body {:?}", body);
-    let node = Node::deserialize(&body).unwrap();
-    logging::log!("node {:?}", node);
-    let d = set_item(&node);
-    logging::log!("new digest {:?}", d.to_hex());
     Ok(())
 }
 
-async fn traverse_async<F: std::ops::AsyncFn(Node) + Clone>(digest: D, f : F) -> leptos::error::Result<()> {
-    let node = get_item(&digest).get_untracked().unwrap();
+async fn traverse_async<S: AsyncStore<Node, D>, F: std::ops::AsyncFn(Node) + Clone>(
+    store: &S,
+    digest: D,
+    f: F,
+) -> anyhow::Result<()> {
+    let node = store
+        .get(&digest)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("node {} not found", digest.to_hex()))?;
     f.clone()(node.clone()).await;
+    if let Some(schema_digest) = node.schema.clone() {
+        Box::pin(traverse_async(store, schema_digest, f.clone())).await?;
+    }
     match node.value {
         Value::Object(object) => {
             for (field_id, field) in object.fields.iter() {
                 for child_digest in field {
-                    Box::pin(traverse_async(child_digest.clone(), f.clone())).await?;
+                    Box::pin(traverse_async(store, child_digest.clone(), f.clone())).await?;
                 }
             }
         }
@@ -565,3 +1058,81 @@ async fn traverse_async<F: std::ops::AsyncFn(Node) + Clone>(digest: D, f : F) ->
     }
     Ok(())
 }
+
+// The nodes reachable from the roots with their digests, each only once. Nodes come in the order
+// `traverse_async` visits them: each node, then its schema, then its children. Nodes for which
+// `get_node` returns `None` are skipped.
+fn reachable<G: FnMut(&D) -> anyhow::Result<Option<Node>>>(
+    roots: &[D],
+    mut get_node: G,
+) -> anyhow::Result<Vec<(D, Node)>> {
+    let mut nodes = vec![];
+    let mut visited = HashSet::new();
+    let mut stack = roots.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some(digest) = stack.pop() {
+        if !visited.insert(digest.clone()) {
+            continue;
+        }
+        let Some(node) = get_node(&digest)? else {
+            continue;
+        };
+        stack.extend(node.children().into_iter().rev());
+        nodes.push((digest, node));
+    }
+    Ok(nodes)
+}
+
+#[test]
+fn test_traverse_async() {
+    let (root, nodes) = test_navigation_graph();
+    let store = test_store(nodes);
+    let visited = std::cell::RefCell::new(vec![]);
+    futures::executor::block_on(traverse_async(&store, root.clone(), |node: Node| {
+        visited.borrow_mut().push(node.id);
+        async {}
+    }))
+    .unwrap();
+    assert_eq!(visited.into_inner(), vec![6, 1, 5, 3, 4, 2]);
+
+    let missing =
+        futures::executor::block_on(traverse_async(&MemoryStore::new(), root, |_: Node| async {}));
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_download_missing() {
+    let (root, nodes) = test_navigation_graph();
+    let fetch = |digest: D| {
+        let served = nodes
+            .get(&digest)
+            .map(Node::serialize)
+            .ok_or(DownloadError::Status {
+                digest,
+                status: 404,
+            });
+        async move { served }
+    };
+    let store = MemoryStore::new();
+    futures::executor::block_on(download_missing(&store, &fetch, root.clone())).unwrap();
+    assert!(nodes.keys().all(|digest| Store::has(&store, digest)));
+
+    // A node served for the wrong digest is not stored, and stops the download.
+    let d = find_value_with(&|d: &D| nodes.get(d).cloned(), &root, &vec![sel(1, 1)]).unwrap();
+    let tampered_fetch = |digest: D| {
+        let served = if digest == d {
+            test_string_node(2, "x")
+        } else {
+            nodes[&digest].clone()
+        };
+        async move { Ok(served.serialize()) }
+    };
+    let store = MemoryStore::new();
+    let result =
+        futures::executor::block_on(download_missing(&store, &tampered_fetch, root.clone()));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("integrity error"));
+    assert!(!Store::has(&store, &d));
+    assert!(Store::has(&store, &root));
+}
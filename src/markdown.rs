@@ -0,0 +1,646 @@
+// Conversion between Markdown and nodes of the `FlatArticle` and `TreeArticle` types in
+// `create_schema`, see https://spec.commonmark.org/
+//
+// Headings, paragraphs, block quotes, code blocks and lists map to article blocks. Text within
+// blocks, including inline markup, is kept as Markdown source.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+
+use crate::{
+    new_id, not_imported, section_heading_level, Imported, Node, ObjectValue, SectionFolder, Value,
+    D, FLAT_ARTICLE_BLOCKS, FLAT_ARTICLE_TYPE_ID, FLAT_BLOCK_CODE, FLAT_BLOCK_H1, FLAT_BLOCK_H2,
+    FLAT_BLOCK_H3, FLAT_BLOCK_HEADINGS, FLAT_BLOCK_ORDERED_LIST, FLAT_BLOCK_PARAGRAPH,
+    FLAT_BLOCK_QUOTE, FLAT_BLOCK_TYPE_ID, FLAT_BLOCK_UNORDERED_LIST, ID, SECTION_BLOCKS,
+    SECTION_SECTIONS, SECTION_TITLE, TREE_ARTICLE_SECTIONS, TREE_ARTICLE_TYPE_ID, TREE_BLOCK_CODE,
+    TREE_BLOCK_ORDERED_LIST, TREE_BLOCK_QUOTE, TREE_BLOCK_TEXT, TREE_BLOCK_TYPE_ID,
+    TREE_BLOCK_UNORDERED_LIST,
+};
+
+// A block of an article, as read from or written to Markdown.
+#[derive(Clone, Debug, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    Quote(String),
+    Code(String),
+    OrderedList(Vec<String>),
+    UnorderedList(Vec<String>),
+}
+
+// A part of the source that is not supported, with the number of the line it starts on.
+type Unmapped = (usize, String);
+
+pub struct MarkdownImport {
+    pub root: D,
+    // Parts of the source that could not be mapped to the article, e.g. "line 3: thematic break".
+    pub unmapped: Vec<String>,
+}
+
+impl Imported for MarkdownImport {
+    fn root(&self) -> &D {
+        &self.root
+    }
+
+    fn messages(&self) -> Vec<String> {
+        not_imported(&self.unmapped)
+    }
+}
+
+// Parses Markdown into nodes of the given article type, which are passed to `put`, and returns the
+// digest of the root node. In a `TreeArticle`, headings start sections, nested by level under a
+// root section without a title.
+pub fn import_markdown<P: FnMut(Node) -> D>(
+    source: &str,
+    article_type_id: ID,
+    put: &mut P,
+) -> anyhow::Result<MarkdownImport> {
+    let (blocks, mut unmapped) = parse_blocks(source);
+    let mut importer = Importer { put };
+    let root = match article_type_id {
+        FLAT_ARTICLE_TYPE_ID => {
+            let blocks = blocks
+                .into_iter()
+                .map(|(line, block)| importer.flat_block(line, block, &mut unmapped))
+                .collect();
+            importer.object(
+                FLAT_ARTICLE_TYPE_ID,
+                BTreeMap::from([(FLAT_ARTICLE_BLOCKS, blocks)]),
+            )
+        }
+        TREE_ARTICLE_TYPE_ID => importer.tree_article(blocks),
+        _ => anyhow::bail!("object type {} is not an article", article_type_id),
+    };
+    unmapped.sort_by_key(|(line, _)| *line);
+    let unmapped = unmapped
+        .into_iter()
+        .map(|(line, message)| format!("line {}: {}", line, message))
+        .collect();
+    Ok(MarkdownImport { root, unmapped })
+}
+
+fn line_number(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+// Splits Markdown into blocks, each with the number of the line it starts on, along with the
+// parts that are not supported.
+fn parse_blocks(source: &str) -> (Vec<(usize, Block)>, Vec<Unmapped>) {
+    let mut blocks = vec![];
+    let mut unmapped = vec![];
+    let mut events = Parser::new(source).into_offset_iter();
+    while let Some((event, range)) = events.next() {
+        let line = line_number(source, range.start);
+        let Event::Start(tag) = event else {
+            unmapped.push((line, describe(&event)));
+            continue;
+        };
+        // The events within the block.
+        let mut inner = vec![];
+        let mut depth = 1;
+        for (event, range) in events.by_ref() {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            inner.push((event, range));
+        }
+        let inner_text = || match (inner.first(), inner.last()) {
+            (Some((_, first)), Some((_, last))) => source[first.start..last.end].trim().to_string(),
+            _ => String::new(),
+        };
+        let block = match tag {
+            Tag::Heading { level, .. } => Block::Heading(level as usize, inner_text()),
+            Tag::Paragraph => Block::Paragraph(inner_text()),
+            Tag::BlockQuote(_) => Block::Quote(quote_text(&source[range.clone()])),
+            Tag::CodeBlock(kind) => {
+                if let CodeBlockKind::Fenced(info) = kind {
+                    if !info.is_empty() {
+                        unmapped.push((line, format!("code block info {:?}", info.as_ref())));
+                    }
+                }
+                let text: String = inner
+                    .iter()
+                    .filter_map(|(event, _)| match event {
+                        Event::Text(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                Block::Code(text.strip_suffix('\n').unwrap_or(&text).to_string())
+            }
+            Tag::List(start) => {
+                let items = list_items(source, &inner);
+                match start {
+                    Some(_) => Block::OrderedList(items),
+                    None => Block::UnorderedList(items),
+                }
+            }
+            _ => {
+                unmapped.push((line, describe(&Event::Start(tag))));
+                continue;
+            }
+        };
+        blocks.push((line, block));
+    }
+    (blocks, unmapped)
+}
+
+fn describe(event: &Event) -> String {
+    match event {
+        Event::Rule => "thematic break".to_string(),
+        Event::Start(Tag::HtmlBlock) | Event::Html(_) => "HTML block".to_string(),
+        event => format!("{:?}", event),
+    }
+}
+
+// The content of a block quote, without the `>` markers.
+fn quote_text(quote: &str) -> String {
+    let lines: Vec<&str> = quote
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            match line.strip_prefix('>') {
+                Some(line) => line.strip_prefix(' ').unwrap_or(line),
+                // A lazy continuation line.
+                None => line,
+            }
+        })
+        .collect();
+    lines.join("\n").trim_end().to_string()
+}
+
+// The content of each item of a list, given the events within the list. Lines after the first are
+// unindented by the width of the list marker.
+fn list_items(source: &str, inner: &[(Event, Range<usize>)]) -> Vec<String> {
+    let mut items = vec![];
+    let mut depth = 0;
+    // The range of the current item, and the offset at which its content starts.
+    let mut item: Option<(Range<usize>, Option<usize>)> = None;
+    for (event, range) in inner {
+        if let Some((_, content @ None)) = &mut item {
+            if depth > 0 && !matches!(event, Event::End(_)) {
+                *content = Some(range.start);
+            }
+        }
+        match event {
+            Event::Start(Tag::Item) if depth == 0 => {
+                item = Some((range.clone(), None));
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some((range, content)) = item.take() {
+                        items.push(item_text(source, range, content));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    items
+}
+
+fn item_text(source: &str, item: Range<usize>, content: Option<usize>) -> String {
+    let Some(start) = content else {
+        return String::new();
+    };
+    let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+    let indent = start - line_start;
+    let lines: Vec<&str> = source[start..item.end.max(start)]
+        .trim_end()
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if index == 0 {
+                line
+            } else {
+                &line[spaces.min(indent)..]
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+struct Importer<'a, P> {
+    put: &'a mut P,
+}
+
+impl<P: FnMut(Node) -> D> Importer<'_, P> {
+    fn node(&mut self, value: Value) -> D {
+        (self.put)(Node {
+            id: new_id(),
+            value,
+            schema: None,
+        })
+    }
+
+    fn object(&mut self, object_type_id: ID, fields: BTreeMap<ID, Vec<D>>) -> D {
+        self.node(Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        }))
+    }
+
+    fn strings(&mut self, strings: &[String]) -> Vec<D> {
+        strings
+            .iter()
+            .map(|s| self.node(Value::String(s.clone())))
+            .collect()
+    }
+
+    // An object of the given type with a single field set to the given strings.
+    fn block(&mut self, object_type_id: ID, field_id: ID, strings: &[String]) -> D {
+        let values = self.strings(strings);
+        self.object(object_type_id, BTreeMap::from([(field_id, values)]))
+    }
+
+    fn flat_block(&mut self, line: usize, block: Block, unmapped: &mut Vec<Unmapped>) -> D {
+        let (field_id, strings) = match block {
+            Block::Heading(level, text) => {
+                if level > 3 {
+                    unmapped.push((line, format!("heading level {} imported as h3", level)));
+                }
+                let field_id = FLAT_BLOCK_HEADINGS[level.min(3) - 1];
+                (field_id, vec![text])
+            }
+            Block::Paragraph(text) => (FLAT_BLOCK_PARAGRAPH, vec![text]),
+            Block::Quote(text) => (FLAT_BLOCK_QUOTE, vec![text]),
+            Block::Code(text) => (FLAT_BLOCK_CODE, vec![text]),
+            Block::OrderedList(items) => (FLAT_BLOCK_ORDERED_LIST, items),
+            Block::UnorderedList(items) => (FLAT_BLOCK_UNORDERED_LIST, items),
+        };
+        self.block(FLAT_BLOCK_TYPE_ID, field_id, &strings)
+    }
+
+    fn tree_article(&mut self, blocks: Vec<(usize, Block)>) -> D {
+        let mut folder = SectionFolder::new();
+        for (_, block) in blocks {
+            let (field_id, strings) = match block {
+                Block::Heading(level, title) => {
+                    let title = self.strings(&[title]).pop();
+                    folder.heading(self.put, level, title);
+                    continue;
+                }
+                Block::Paragraph(text) => (TREE_BLOCK_TEXT, vec![text]),
+                Block::Quote(text) => (TREE_BLOCK_QUOTE, vec![text]),
+                Block::Code(text) => (TREE_BLOCK_CODE, vec![text]),
+                Block::OrderedList(items) => (TREE_BLOCK_ORDERED_LIST, items),
+                Block::UnorderedList(items) => (TREE_BLOCK_UNORDERED_LIST, items),
+            };
+            folder.block(self.block(TREE_BLOCK_TYPE_ID, field_id, &strings));
+        }
+        let article = folder.finish(self.put);
+        self.node(Value::Object(article))
+    }
+}
+
+// Writes the `FlatArticle` or `TreeArticle` document rooted at the given digest as CommonMark.
+pub fn export_markdown<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    root: &D,
+) -> anyhow::Result<String> {
+    let exporter = Exporter { get_node };
+    let article = exporter.object(root)?;
+    let mut blocks = vec![];
+    match article.object_type_id {
+        FLAT_ARTICLE_TYPE_ID => {
+            for digest in article
+                .fields
+                .get(&FLAT_ARTICLE_BLOCKS)
+                .into_iter()
+                .flatten()
+            {
+                exporter.flat_block(digest, &mut blocks)?;
+            }
+        }
+        TREE_ARTICLE_TYPE_ID => {
+            for digest in article
+                .fields
+                .get(&TREE_ARTICLE_SECTIONS)
+                .into_iter()
+                .flatten()
+            {
+                exporter.section(digest, 0, &mut blocks)?;
+            }
+        }
+        object_type_id => anyhow::bail!("object type {} is not an article", object_type_id),
+    }
+    Ok(render(&blocks))
+}
+
+// A field of a block type, and how its values are written.
+type BlockField = (ID, fn(Vec<String>) -> Block);
+
+struct Exporter<'a, G> {
+    get_node: &'a G,
+}
+
+impl<G: Fn(&D) -> Option<Node>> Exporter<'_, G> {
+    fn node(&self, digest: &D) -> anyhow::Result<Node> {
+        (self.get_node)(digest).ok_or_else(|| anyhow::anyhow!("node {} not found", digest.to_hex()))
+    }
+
+    fn object(&self, digest: &D) -> anyhow::Result<ObjectValue> {
+        match self.node(digest)?.value {
+            Value::Object(object) => Ok(object),
+            _ => anyhow::bail!("node {} is not an object", digest.to_hex()),
+        }
+    }
+
+    fn strings(&self, object: &ObjectValue, field_id: ID) -> anyhow::Result<Vec<String>> {
+        object
+            .fields
+            .get(&field_id)
+            .into_iter()
+            .flatten()
+            .map(|digest| match self.node(digest)?.value {
+                Value::String(s) => Ok(s),
+                value => anyhow::bail!("expected a string, found {}", value),
+            })
+            .collect()
+    }
+
+    // Adds the blocks for each field that is set, in the order in which they are listed; blocks
+    // are expected to set exactly one.
+    fn blocks(
+        &self,
+        object: &ObjectValue,
+        fields: &[BlockField],
+        blocks: &mut Vec<Block>,
+    ) -> anyhow::Result<()> {
+        for (field_id, block) in fields {
+            let strings = self.strings(object, *field_id)?;
+            if !strings.is_empty() {
+                blocks.push(block(strings));
+            }
+        }
+        Ok(())
+    }
+
+    fn flat_block(&self, digest: &D, blocks: &mut Vec<Block>) -> anyhow::Result<()> {
+        let block = self.object(digest)?;
+        self.blocks(
+            &block,
+            &[
+                (FLAT_BLOCK_H1, |s| Block::Heading(1, s.concat())),
+                (FLAT_BLOCK_H2, |s| Block::Heading(2, s.concat())),
+                (FLAT_BLOCK_H3, |s| Block::Heading(3, s.concat())),
+                (FLAT_BLOCK_PARAGRAPH, |s| Block::Paragraph(s.concat())),
+                (FLAT_BLOCK_QUOTE, |s| Block::Quote(s.concat())),
+                (FLAT_BLOCK_CODE, |s| Block::Code(s.concat())),
+                (FLAT_BLOCK_ORDERED_LIST, Block::OrderedList),
+                (FLAT_BLOCK_UNORDERED_LIST, Block::UnorderedList),
+            ],
+            blocks,
+        )
+    }
+
+    // Adds the blocks of a section at the given depth, where the root section is at depth 0 and
+    // only has a heading if it has a title.
+    fn section(&self, digest: &D, depth: usize, blocks: &mut Vec<Block>) -> anyhow::Result<()> {
+        let section = self.object(digest)?;
+        let title = self.strings(&section, SECTION_TITLE)?;
+        let depth = match section_heading_level(depth, !title.is_empty()) {
+            Some(level) => {
+                // Markdown has no headings below level 6.
+                blocks.push(Block::Heading(level.min(6), title.concat()));
+                level
+            }
+            None => depth,
+        };
+        for digest in section.fields.get(&SECTION_BLOCKS).into_iter().flatten() {
+            let block = self.object(digest)?;
+            self.blocks(
+                &block,
+                &[
+                    (TREE_BLOCK_TEXT, |s| Block::Paragraph(s.concat())),
+                    (TREE_BLOCK_QUOTE, |s| Block::Quote(s.concat())),
+                    (TREE_BLOCK_CODE, |s| Block::Code(s.concat())),
+                    (TREE_BLOCK_ORDERED_LIST, Block::OrderedList),
+                    (TREE_BLOCK_UNORDERED_LIST, Block::UnorderedList),
+                ],
+                blocks,
+            )?;
+        }
+        for digest in section.fields.get(&SECTION_SECTIONS).into_iter().flatten() {
+            self.section(digest, depth + 1, blocks)?;
+        }
+        Ok(())
+    }
+}
+
+fn render(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    // The list marker of the previous block, if it was a list. Consecutive lists alternate
+    // markers, since they would otherwise be read back as a single list.
+    let mut previous_marker = None;
+    for block in blocks {
+        let mut marker = None;
+        let text = match block {
+            Block::Heading(level, text) => format!("{} {}", "#".repeat(*level), text)
+                .trim_end()
+                .to_string(),
+            Block::Paragraph(text) => text.clone(),
+            Block::Quote(text) => {
+                let lines: Vec<String> = text
+                    .split('\n')
+                    .map(|line| format!("> {}", line).trim_end().to_string())
+                    .collect();
+                lines.join("\n")
+            }
+            Block::Code(text) => {
+                // A fence longer than any run of backticks in the code.
+                let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                if text.is_empty() {
+                    format!("{}\n{}", fence, fence)
+                } else {
+                    format!("{}\n{}\n{}", fence, text, fence)
+                }
+            }
+            Block::OrderedList(items) => {
+                let delimiter = if previous_marker == Some('.') {
+                    ')'
+                } else {
+                    '.'
+                };
+                marker = Some(delimiter);
+                render_items(items, |index| format!("{}{} ", index + 1, delimiter))
+            }
+            Block::UnorderedList(items) => {
+                let bullet = if previous_marker == Some('-') {
+                    '*'
+                } else {
+                    '-'
+                };
+                marker = Some(bullet);
+                render_items(items, |_| format!("{} ", bullet))
+            }
+        };
+        previous_marker = marker;
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&text);
+        out.push('\n');
+    }
+    out
+}
+
+// Writes list items, with lines after the first indented to the content of the item.
+fn render_items(items: &[String], marker: impl Fn(usize) -> String) -> String {
+    let lines: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let marker = marker(index);
+            let indent = " ".repeat(marker.len());
+            let lines: Vec<String> = item
+                .split('\n')
+                .enumerate()
+                .map(|(index, line)| match index {
+                    0 => format!("{}{}", marker, line).trim_end().to_string(),
+                    _ if line.is_empty() => String::new(),
+                    _ => format!("{}{}", indent, line),
+                })
+                .collect();
+            lines.join("\n")
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+use crate::{MemoryStore, Store};
+
+#[cfg(test)]
+const TEST_MARKDOWN: &str = r#"# Title
+
+Some *emphasis* and a [link](https://example.com),
+on two lines.
+
+## Lists
+
+- one
+- two
+  continued
+
+* three
+
+1. first
+2. second
+
+1) again
+
+> A quote
+>
+> over two paragraphs
+
+### Code
+
+````
+```nested fence```
+````
+"#;
+
+#[cfg(test)]
+fn test_markdown_round_trip(
+    article_type_id: ID,
+    source: &str,
+) -> (MarkdownImport, MemoryStore<Node, D>, String) {
+    let store = MemoryStore::new();
+    let import =
+        import_markdown(source, article_type_id, &mut |node: Node| store.put(node)).unwrap();
+    let exported = export_markdown(&|digest: &D| store.get(digest), &import.root).unwrap();
+    (import, store, exported)
+}
+
+#[test]
+fn test_import_markdown_flat() {
+    let source = "#### Deep\n\ntext\n\n---\n\n```rust\nfn main() {}\n```\n";
+    let (import, store, _) = test_markdown_round_trip(FLAT_ARTICLE_TYPE_ID, source);
+    assert_eq!(
+        import.unmapped,
+        vec![
+            "line 1: heading level 4 imported as h3",
+            "line 5: thematic break",
+            "line 7: code block info \"rust\"",
+        ]
+    );
+    let Value::Object(article) = store.get(&import.root).unwrap().value else {
+        panic!("expected object");
+    };
+    let blocks: Vec<_> = article.fields[&FLAT_ARTICLE_BLOCKS]
+        .iter()
+        .map(|digest| match store.get(digest).unwrap().value {
+            Value::Object(block) => block
+                .fields
+                .iter()
+                .map(|(field_id, digests)| (*field_id, store.get(&digests[0]).unwrap().value))
+                .collect::<Vec<_>>(),
+            value => panic!("expected object, found {}", value),
+        })
+        .collect();
+    let string = |s: &str| Value::String(s.to_string());
+    assert_eq!(
+        blocks,
+        vec![
+            vec![(FLAT_BLOCK_H3, string("Deep"))],
+            vec![(FLAT_BLOCK_PARAGRAPH, string("text"))],
+            vec![(FLAT_BLOCK_CODE, string("fn main() {}"))],
+        ]
+    );
+}
+
+#[test]
+fn test_import_markdown_tree() {
+    let source = "intro\n\n# A\n\n## A.1\n\ntext\n\n# B\n";
+    let (import, store, exported) = test_markdown_round_trip(TREE_ARTICLE_TYPE_ID, source);
+    assert_eq!(exported, source);
+    let object = |digest: &D| match store.get(digest).unwrap().value {
+        Value::Object(object) => object,
+        value => panic!("expected object, found {}", value),
+    };
+    // Titles of the subsections of a section.
+    let titles = |section: &ObjectValue| -> Vec<Value> {
+        section.fields[&SECTION_SECTIONS]
+            .iter()
+            .map(|digest| {
+                store
+                    .get(&object(digest).fields[&SECTION_TITLE][0])
+                    .unwrap()
+                    .value
+            })
+            .collect()
+    };
+    let root = object(&object(&import.root).fields[&TREE_ARTICLE_SECTIONS][0]);
+    assert!(!root.fields.contains_key(&SECTION_TITLE));
+    assert_eq!(root.fields[&SECTION_BLOCKS].len(), 1);
+    assert_eq!(
+        titles(&root),
+        vec![
+            Value::String("A".to_string()),
+            Value::String("B".to_string())
+        ]
+    );
+    let a = object(&root.fields[&SECTION_SECTIONS][0]);
+    assert_eq!(titles(&a), vec![Value::String("A.1".to_string())]);
+}
+
+#[test]
+fn test_export_markdown_round_trip() {
+    for article_type_id in [FLAT_ARTICLE_TYPE_ID, TREE_ARTICLE_TYPE_ID] {
+        let (import, _, exported) = test_markdown_round_trip(article_type_id, TEST_MARKDOWN);
+        assert!(import.unmapped.is_empty());
+        assert_eq!(exported, TEST_MARKDOWN);
+    }
+}
@@ -0,0 +1,424 @@
+// Three-way merge of document trees.
+//
+// Two edits of the same root produce two roots that share every node neither of them changed, so
+// the merge walks the three trees together and only descends into nodes that differ. Entries of
+// repeated fields are matched by position: changes to existing entries and appends are merged,
+// anything else that both sides did to a field is a conflict. Entries that both sides appended,
+// e.g. after one side merged the other, are kept only once.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{format_path, Node, ObjectValue, Path, Selector, Store, Value, D, ID};
+
+#[derive(Clone)]
+pub struct Merge {
+    // Root of the merged tree, with our side of each conflict.
+    pub root: D,
+    pub conflicts: Vec<Conflict>,
+}
+
+// Entries that were changed in different ways on each side, starting at `path`. This is a single
+// entry if both sides changed the same value, or all the entries of a field if at least one side
+// inserted, deleted or reordered them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub path: Path,
+    pub base: Vec<D>,
+    pub ours: Vec<D>,
+    pub theirs: Vec<D>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Base,
+    Ours,
+    Theirs,
+}
+
+impl Side {
+    pub const ALL: &'static [Side] = &[Side::Base, Side::Ours, Side::Theirs];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::Base => "Base",
+            Side::Ours => "Ours",
+            Side::Theirs => "Theirs",
+        }
+    }
+}
+
+impl Conflict {
+    pub fn entries(&self, side: Side) -> &[D] {
+        match side {
+            Side::Base => &self.base,
+            Side::Ours => &self.ours,
+            Side::Theirs => &self.theirs,
+        }
+    }
+}
+
+// Merges the changes from `base` to `theirs` into `ours`, storing the new nodes.
+pub fn merge<S: Store<Node, D>>(
+    store: &S,
+    base: &D,
+    ours: &D,
+    theirs: &D,
+) -> anyhow::Result<Merge> {
+    let mut merger = Merger {
+        store,
+        conflicts: vec![],
+    };
+    let root = merger.value(base, ours, theirs, &Path::default())?;
+    Ok(Merge {
+        root,
+        conflicts: merger.conflicts,
+    })
+}
+
+// Replaces our side of a conflict in the merged tree with the given side. Resolving a conflict
+// does not move the entries of any other conflict of the same merge.
+pub fn resolve_conflict<S: Store<Node, D>>(
+    store: &S,
+    root: &D,
+    conflict: &Conflict,
+    side: Side,
+) -> anyhow::Result<D> {
+    splice(
+        store,
+        root,
+        &conflict.path,
+        conflict.ours.len(),
+        conflict.entries(side),
+    )
+}
+
+struct Merger<'a, S> {
+    store: &'a S,
+    conflicts: Vec<Conflict>,
+}
+
+impl<S: Store<Node, D>> Merger<'_, S> {
+    fn get(&self, digest: &D) -> anyhow::Result<Node> {
+        self.store
+            .get(digest)
+            .ok_or_else(|| anyhow::anyhow!("node {} not found", digest.to_hex()))
+    }
+
+    fn value(&mut self, base: &D, ours: &D, theirs: &D, path: &Path) -> anyhow::Result<D> {
+        if let Some(merged) = merge_unchanged(base, ours, theirs) {
+            return Ok(merged.clone());
+        }
+        let base_node = self.get(base)?;
+        let our_node = self.get(ours)?;
+        let their_node = self.get(theirs)?;
+        let (Value::Object(base_object), Value::Object(our_object), Value::Object(their_object)) =
+            (&base_node.value, &our_node.value, &their_node.value)
+        else {
+            return Ok(self.value_conflict(path, base, ours, theirs));
+        };
+        if our_object.object_type_id != base_object.object_type_id
+            || their_object.object_type_id != base_object.object_type_id
+        {
+            return Ok(self.value_conflict(path, base, ours, theirs));
+        }
+
+        let field_ids = [base_object, our_object, their_object]
+            .iter()
+            .flat_map(|object| object.fields.keys().cloned())
+            .collect::<BTreeSet<_>>();
+        let mut fields = BTreeMap::new();
+        for field_id in field_ids {
+            let entries =
+                |object: &ObjectValue| object.fields.get(&field_id).cloned().unwrap_or_default();
+            let merged = self.field(
+                field_id,
+                &entries(base_object),
+                &entries(our_object),
+                &entries(their_object),
+                path,
+            )?;
+            // Removing an empty field is merged like any other change, so that merging a side
+            // with itself does not change its digest.
+            let has_field = |object: &ObjectValue| object.fields.contains_key(&field_id);
+            let present = *merge_unchanged(
+                &has_field(base_object),
+                &has_field(our_object),
+                &has_field(their_object),
+            )
+            .unwrap_or(&true);
+            if present || !merged.is_empty() {
+                fields.insert(field_id, merged);
+            }
+        }
+        Ok(self.store.put(Node {
+            id: *merge_unchanged(&base_node.id, &our_node.id, &their_node.id)
+                .unwrap_or(&our_node.id),
+            value: Value::Object(ObjectValue {
+                object_type_id: base_object.object_type_id,
+                fields,
+            }),
+            // The schema is only set on the root, and editing the schema makes it point to the
+            // new version; ours is kept if both sides changed it.
+            schema: merge_unchanged(&base_node.schema, &our_node.schema, &their_node.schema)
+                .unwrap_or(&our_node.schema)
+                .clone(),
+        }))
+    }
+
+    fn field(
+        &mut self,
+        field_id: ID,
+        base: &[D],
+        ours: &[D],
+        theirs: &[D],
+        path: &Path,
+    ) -> anyhow::Result<Vec<D>> {
+        if let Some(merged) = merge_unchanged(base, ours, theirs) {
+            return Ok(merged.to_vec());
+        }
+        let mut first_path = path.clone();
+        first_path.push(Selector { field_id, index: 0 });
+        if !edits_in_place_or_appends(base, ours) || !edits_in_place_or_appends(base, theirs) {
+            self.conflict(first_path, base, ours, theirs);
+            return Ok(ours.to_vec());
+        }
+        let mut merged = vec![];
+        for index in 0..base.len() {
+            let mut entry_path = path.clone();
+            entry_path.push(Selector { field_id, index });
+            merged.push(self.value(&base[index], &ours[index], &theirs[index], &entry_path)?);
+        }
+        let our_appends = &ours[base.len()..];
+        let their_appends = &theirs[base.len()..];
+        let common = our_appends
+            .iter()
+            .zip(their_appends)
+            .take_while(|(ours, theirs)| ours == theirs)
+            .count();
+        merged.extend_from_slice(our_appends);
+        merged.extend_from_slice(&their_appends[common..]);
+        Ok(merged)
+    }
+
+    // Records a conflict. Our side is used in the merged tree until it is resolved.
+    fn conflict(&mut self, path: Path, base: &[D], ours: &[D], theirs: &[D]) {
+        self.conflicts.push(Conflict {
+            path,
+            base: base.to_vec(),
+            ours: ours.to_vec(),
+            theirs: theirs.to_vec(),
+        });
+    }
+
+    fn value_conflict(&mut self, path: &Path, base: &D, ours: &D, theirs: &D) -> D {
+        self.conflict(
+            path.clone(),
+            std::slice::from_ref(base),
+            std::slice::from_ref(ours),
+            std::slice::from_ref(theirs),
+        );
+        ours.clone()
+    }
+}
+
+// The result of the merge if at most one side differs from the base.
+fn merge_unchanged<'a, T: PartialEq + ?Sized>(
+    base: &T,
+    ours: &'a T,
+    theirs: &'a T,
+) -> Option<&'a T> {
+    if ours == theirs || base == theirs {
+        Some(ours)
+    } else if base == ours {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+// Whether the entries of a field were only changed in place or appended to, rather than inserted,
+// deleted or reordered. Changed entries cannot be told apart from replaced ones, so a side that
+// appends has to leave the existing entries alone.
+fn edits_in_place_or_appends(base: &[D], side: &[D]) -> bool {
+    side.len() == base.len() || side.starts_with(base)
+}
+
+// Replaces `len` entries at the given path with `entries`, storing the nodes along the path.
+fn splice<S: Store<Node, D>>(
+    store: &S,
+    root: &D,
+    path: &[Selector],
+    len: usize,
+    entries: &[D],
+) -> anyhow::Result<D> {
+    let Some((selector, rest)) = path.split_first() else {
+        // The root is always a single entry.
+        return entries
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("the root cannot be removed"));
+    };
+    let mut node = store
+        .get(root)
+        .ok_or_else(|| anyhow::anyhow!("node {} not found", root.to_hex()))?;
+    let Value::Object(object) = &mut node.value else {
+        anyhow::bail!("node {} is not an object", root.to_hex());
+    };
+    let field = object.fields.entry(selector.field_id).or_default();
+    let end = selector.index + if rest.is_empty() { len } else { 1 };
+    if end > field.len() {
+        anyhow::bail!(
+            "{} is out of bounds in node {}",
+            format_path(&path.to_vec()),
+            root.to_hex()
+        );
+    }
+    if rest.is_empty() {
+        field.splice(selector.index..end, entries.iter().cloned());
+    } else {
+        field[selector.index] = splice(store, &field[selector.index], rest, len, entries)?;
+    }
+    Ok(store.put(node))
+}
+
+#[cfg(test)]
+use crate::{find_value_with, test_object_node, test_string_node, update_node, MemoryStore};
+#[cfg(test)]
+use maplit::btreemap;
+
+#[cfg(test)]
+fn test_path(selectors: &[(ID, usize)]) -> Path {
+    selectors
+        .iter()
+        .map(|&(field_id, index)| Selector { field_id, index })
+        .collect()
+}
+
+#[cfg(test)]
+fn test_entries(store: &MemoryStore<Node, D>, root: &D, path: &Path) -> Vec<String> {
+    let get_node = |digest: &D| store.get(digest);
+    let object = find_value_with(&get_node, root, path).and_then(|d| store.get(&d));
+    let Some(Node {
+        value: Value::Object(object),
+        ..
+    }) = object
+    else {
+        panic!("no object at {}", format_path(path));
+    };
+    object
+        .fields
+        .values()
+        .flatten()
+        .map(|d| match store.get(d).map(|node| node.value) {
+            Some(Value::String(s)) => s,
+            _ => "(object)".to_string(),
+        })
+        .collect()
+}
+
+// A document with a title in field 0, tags in field 1, and an author in field 2 whose name is in
+// field 0.
+#[cfg(test)]
+fn test_base(store: &MemoryStore<Node, D>) -> D {
+    let title = store.put(test_string_node(0, "title"));
+    let tag = store.put(test_string_node(0, "a"));
+    let name = store.put(test_string_node(0, "name"));
+    let author = store.put(test_object_node(0, 1, btreemap! { 0 => vec![name] }));
+    store.put(test_object_node(
+        0,
+        1,
+        btreemap! { 0 => vec![title], 1 => vec![tag], 2 => vec![author] },
+    ))
+}
+
+#[cfg(test)]
+fn test_set_string(store: &MemoryStore<Node, D>, root: &D, path: &[(ID, usize)], s: &str) -> D {
+    update_node(store, root, &test_path(path), Value::String(s.to_string()))
+}
+
+#[test]
+fn test_merge_without_conflicts() {
+    let store = MemoryStore::new();
+    let base = test_base(&store);
+    // Changes to different fields, and to different fields of a nested object.
+    let ours = test_set_string(&store, &base, &[(0, 0)], "our title");
+    let theirs = test_set_string(&store, &base, &[(2, 0), (0, 0)], "their name");
+    // Appends to the same repeated field.
+    let b = store.put(test_string_node(0, "b"));
+    let c = store.put(test_string_node(0, "c"));
+    let ours = splice(&store, &ours, &test_path(&[(1, 1)]), 0, &[b]).unwrap();
+    let theirs = splice(&store, &theirs, &test_path(&[(1, 1)]), 0, &[c]).unwrap();
+
+    let merged = merge(&store, &base, &ours, &theirs).unwrap();
+    assert_eq!(merged.conflicts, vec![]);
+    assert_eq!(
+        test_entries(&store, &merged.root, &vec![]),
+        vec!["our title", "a", "b", "c", "(object)"]
+    );
+    assert_eq!(
+        test_entries(&store, &merged.root, &test_path(&[(2, 0)])),
+        vec!["their name"]
+    );
+
+    // Merging with an unchanged side gives the other side.
+    assert_eq!(merge(&store, &base, &ours, &base).unwrap().root, ours);
+    assert_eq!(merge(&store, &base, &base, &theirs).unwrap().root, theirs);
+    assert_eq!(merge(&store, &base, &ours, &ours).unwrap().root, ours);
+}
+
+#[test]
+fn test_merge_common_appends() {
+    let store = MemoryStore::new();
+    let base = test_base(&store);
+    let b = store.put(test_string_node(0, "b"));
+    let c = store.put(test_string_node(0, "c"));
+    let ours = splice(&store, &base, &test_path(&[(1, 1)]), 0, &[b.clone()]).unwrap();
+    let theirs = splice(&store, &base, &test_path(&[(1, 1)]), 0, &[b, c]).unwrap();
+
+    let merged = merge(&store, &base, &ours, &theirs).unwrap();
+    assert_eq!(merged.conflicts, vec![]);
+    assert_eq!(merged.root, theirs);
+    assert_eq!(
+        test_entries(&store, &merged.root, &vec![]),
+        vec!["title", "a", "b", "c", "(object)"]
+    );
+}
+
+#[test]
+fn test_merge_conflicts() {
+    let store = MemoryStore::new();
+    let base = test_base(&store);
+    let ours = test_set_string(&store, &base, &[(2, 0), (0, 0)], "our name");
+    let theirs = test_set_string(&store, &base, &[(2, 0), (0, 0)], "their name");
+    // Deleting an entry on one side and changing it on the other.
+    let ours = splice(&store, &ours, &test_path(&[(1, 0)]), 1, &[]).unwrap();
+    let theirs = test_set_string(&store, &theirs, &[(1, 0)], "b");
+
+    let merged = merge(&store, &base, &ours, &theirs).unwrap();
+    let paths = merged
+        .conflicts
+        .iter()
+        .map(|conflict| format_path(&conflict.path))
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["1[0]", "2[0].0[0]"]);
+    // Our side is kept until resolved.
+    assert_eq!(
+        test_entries(&store, &merged.root, &vec![]),
+        vec!["title", "(object)"]
+    );
+    assert_eq!(
+        test_entries(&store, &merged.root, &test_path(&[(2, 0)])),
+        vec!["our name"]
+    );
+
+    let mut root = merged.root.clone();
+    for conflict in &merged.conflicts {
+        root = resolve_conflict(&store, &root, conflict, Side::Theirs).unwrap();
+    }
+    assert_eq!(root, theirs);
+    let root = resolve_conflict(&store, &merged.root, &merged.conflicts[0], Side::Base).unwrap();
+    assert_eq!(
+        test_entries(&store, &root, &vec![]),
+        vec!["title", "a", "(object)"]
+    );
+}
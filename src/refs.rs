@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use leptos::SignalGetUntracked;
+use serde::{Deserialize, Serialize};
+
+use crate::{storage, D};
+
+const REFS_KEY: &str = "refs";
+
+// Root digests saved under a name, so that a document can be found again after editing others.
+// Like git refs, a name points to a single root and can be moved to a newer one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Refs {
+    refs: BTreeMap<String, D>,
+}
+
+impl Refs {
+    pub fn get(&self, name: &str) -> Option<&D> {
+        self.refs.get(name)
+    }
+
+    // Refs in order of name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &D)> {
+        self.refs.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    // Roots that are protected from garbage collection.
+    pub fn digests(&self) -> Vec<D> {
+        self.refs.values().cloned().collect()
+    }
+
+    // Points the name to the given root, creating the ref if it does not exist yet.
+    pub fn set(&mut self, name: &str, root: D) -> anyhow::Result<()> {
+        let name = validate_name(name)?;
+        self.refs.insert(name.to_string(), root);
+        Ok(())
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        let to = validate_name(to)?;
+        if from == to {
+            return Ok(());
+        }
+        if self.refs.contains_key(to) {
+            anyhow::bail!("ref {:?} already exists", to);
+        }
+        let root = self
+            .refs
+            .remove(from)
+            .ok_or_else(|| anyhow::anyhow!("ref {:?} not found", from))?;
+        self.refs.insert(to.to_string(), root);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<D> {
+        self.refs.remove(name)
+    }
+}
+
+fn validate_name(name: &str) -> anyhow::Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("ref name is empty");
+    }
+    Ok(name)
+}
+
+pub fn load_refs() -> Refs {
+    let serialized = storage::get_value(REFS_KEY).get_untracked();
+    serde_json::from_str(&serialized).unwrap_or_default()
+}
+
+pub fn save_refs(refs: &Refs) {
+    storage::set_value(REFS_KEY, &serde_json::to_string(refs).unwrap());
+}
+
+#[cfg(test)]
+use crate::test_digest;
+
+#[test]
+fn test_refs() {
+    let mut refs = Refs::default();
+    refs.set(" main ", test_digest(1)).unwrap();
+    refs.set("draft", test_digest(2)).unwrap();
+    assert_eq!(refs.get("main"), Some(&test_digest(1)));
+    // Setting an existing ref moves it.
+    refs.set("main", test_digest(3)).unwrap();
+    assert_eq!(
+        refs.iter().collect::<Vec<_>>(),
+        vec![
+            (&"draft".to_string(), &test_digest(2)),
+            (&"main".to_string(), &test_digest(3))
+        ]
+    );
+    assert_eq!(
+        refs.set("  ", test_digest(4)).unwrap_err().to_string(),
+        "ref name is empty"
+    );
+
+    assert_eq!(
+        refs.rename("draft", "main").unwrap_err().to_string(),
+        "ref \"main\" already exists"
+    );
+    assert_eq!(
+        refs.rename("missing", "other").unwrap_err().to_string(),
+        "ref \"missing\" not found"
+    );
+    refs.rename("draft", "published").unwrap();
+    assert_eq!(refs.get("draft"), None);
+    assert_eq!(refs.get("published"), Some(&test_digest(2)));
+
+    assert_eq!(refs.remove("main"), Some(test_digest(3)));
+    assert_eq!(refs.digests(), vec![test_digest(2)]);
+
+    let serialized = serde_json::to_string(&refs).unwrap();
+    assert_eq!(serde_json::from_str::<Refs>(&serialized).unwrap(), refs);
+}
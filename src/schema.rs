@@ -1,18 +1,46 @@
+use crate::validate_document;
 use crate::FieldType;
+use crate::HasDigest;
+use crate::Node;
 use crate::ObjectType;
+use crate::ObjectValue;
 use crate::Schema;
+use crate::Store;
 use crate::Type;
+use crate::Value;
+use crate::D;
+use crate::ID;
+use leptos::logging;
 use maplit::btreemap;
 use maplit::hashmap;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+// Like `btreemap!` / `hashmap!`, but panics on duplicate keys instead of silently keeping the last
+// entry, which a `Schema` could not represent and therefore `Schema::validate` could not detect.
+macro_rules! unique_map {
+    ($($key:expr => $value:expr),* $(,)?) => {{
+        let mut map = BTreeMap::new();
+        $(
+            let key = $key;
+            if map.insert(key, $value).is_some() {
+                panic!("duplicate id {}", key);
+            }
+        )*
+        map.into_iter().collect()
+    }};
+}
 
 pub fn create_schema() -> Schema {
     Schema {
         root_object_type_id: 2325,
-        object_types: hashmap! {
+        object_types: unique_map! {
             // https://doc.rust-lang.org/cargo/reference/manifest.html
             893728943 => ObjectType {
                 name: "CargoManifest".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     0 => FieldType {
                         name: "package".to_string(),
                         type_: Type::Object(87839159),
@@ -35,57 +63,57 @@ pub fn create_schema() -> Schema {
                     },
                     4 => FieldType {
                         name: "features".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::Object(4128931),
                         repeated: true,
                     },
                     5 => FieldType {
                         name: "target".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::Object(9812733),
                         repeated: true,
                     },
                     6 => FieldType {
                         name: "workspace".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::Object(2389112),
                         repeated: false,
                     },
                     7 => FieldType {
                         name: "profile".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::Object(7723019),
                         repeated: true,
                     },
                     8 => FieldType {
                         name: "patch".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::Object(5510293),
                         repeated: true,
                     },
                     9 => FieldType {
                         name: "replace".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::Object(7218972),
                         repeated: true,
                     },
                     10 => FieldType {
                         name: "workspace-members".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::String,
                         repeated: true,
                     },
                     11 => FieldType {
                         name: "default-members".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::String,
                         repeated: true,
                     },
                     12 => FieldType {
                         name: "exclude".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::String,
                         repeated: true,
                     },
                     13 => FieldType {
                         name: "include".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::String,
                         repeated: true,
                     },
                     14 => FieldType {
                         name: "metadata".to_string(),
-                        type_: Type::Object(893728943),
+                        type_: Type::String,
                         repeated: false,
                     },
                 },
@@ -93,7 +121,7 @@ pub fn create_schema() -> Schema {
             // package
             87839159 => ObjectType {
                 name: "Package".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     0 => FieldType {
                         name: "name".to_string(),
                         type_: Type::String,
@@ -109,11 +137,6 @@ pub fn create_schema() -> Schema {
                         type_: Type::String,
                         repeated: true,
                     },
-                    3 => FieldType {
-                        name: "edition".to_string(),
-                        type_: Type::String,
-                        repeated: false,
-                    },
                     4 => FieldType {
                         name: "build".to_string(),
                         type_: Type::String,
@@ -156,9 +179,129 @@ pub fn create_schema() -> Schema {
                     },
                 },
             },
+            // https://doc.rust-lang.org/cargo/reference/features.html
+            4128931 => ObjectType {
+                name: "Feature".to_string(),
+                fields: unique_map! {
+                    0 => FieldType {
+                        name: "name".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "enables".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
+                },
+            },
+            // https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#platform-specific-dependencies
+            9812733 => ObjectType {
+                name: "Target".to_string(),
+                fields: unique_map! {
+                    0 => FieldType {
+                        name: "cfg".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "dependencies".to_string(),
+                        type_: Type::Object(7218972),
+                        repeated: true,
+                    },
+                    2 => FieldType {
+                        name: "dev-dependencies".to_string(),
+                        type_: Type::Object(7218972),
+                        repeated: true,
+                    },
+                    3 => FieldType {
+                        name: "build-dependencies".to_string(),
+                        type_: Type::Object(7218972),
+                        repeated: true,
+                    },
+                },
+            },
+            // https://doc.rust-lang.org/cargo/reference/workspaces.html
+            2389112 => ObjectType {
+                name: "Workspace".to_string(),
+                fields: unique_map! {
+                    0 => FieldType {
+                        name: "members".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
+                    1 => FieldType {
+                        name: "default-members".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
+                    2 => FieldType {
+                        name: "exclude".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
+                    3 => FieldType {
+                        name: "resolver".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                },
+            },
+            // https://doc.rust-lang.org/cargo/reference/profiles.html
+            7723019 => ObjectType {
+                name: "Profile".to_string(),
+                fields: unique_map! {
+                    0 => FieldType {
+                        name: "name".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "opt-level".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    2 => FieldType {
+                        name: "debug".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    3 => FieldType {
+                        name: "lto".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    4 => FieldType {
+                        name: "panic".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    5 => FieldType {
+                        name: "codegen-units".to_string(),
+                        type_: Type::Int,
+                        repeated: false,
+                    },
+                },
+            },
+            // https://doc.rust-lang.org/cargo/reference/overriding-dependencies.html#the-patch-section
+            5510293 => ObjectType {
+                name: "Patch".to_string(),
+                fields: unique_map! {
+                    0 => FieldType {
+                        name: "registry".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "dependencies".to_string(),
+                        type_: Type::Object(7218972),
+                        repeated: true,
+                    },
+                },
+            },
             7218972 => ObjectType {
                 name: "Dependency".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     12093 => FieldType {
                         name: "name".to_string(),
                         type_: Type::String,
@@ -199,13 +342,33 @@ pub fn create_schema() -> Schema {
                         type_: Type::String,
                         repeated: false,
                     },
+                    4471923 => FieldType {
+                        name: "features".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
+                    8812370 => FieldType {
+                        name: "default-features".to_string(),
+                        type_: Type::Boolean,
+                        repeated: false,
+                    },
+                    3390127 => FieldType {
+                        name: "optional".to_string(),
+                        type_: Type::Boolean,
+                        repeated: false,
+                    },
+                    6620418 => FieldType {
+                        name: "package".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
                 },
             },
 
             // Dockerfile
             213792873 => ObjectType {
                 name: "Dockerfile".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     2871232 => FieldType {
                         name: "commands".to_string(),
                         type_: Type::Object(28973111),
@@ -215,7 +378,7 @@ pub fn create_schema() -> Schema {
             },
             28973111 => ObjectType {
                 name: "Dockerfile Command".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     128371 => FieldType {
                         name: "from".to_string(),
                         type_: Type::Object(29187312),
@@ -231,12 +394,12 @@ pub fn create_schema() -> Schema {
                         type_: Type::String,
                         repeated: true,
                     },
-                    129837 => FieldType {
+                    2918371 => FieldType {
                         name: "label".to_string(),
                         type_: Type::String,
                         repeated: true,
                     },
-                    129837 => FieldType {
+                    7712391 => FieldType {
                         name: "expose".to_string(),
                         type_: Type::String,
                         repeated: true,
@@ -301,13 +464,19 @@ pub fn create_schema() -> Schema {
                         type_: Type::String,
                         repeated: true,
                     },
+                    // Whether the arguments are in exec form, e.g. `CMD ["echo", "hi"]`.
+                    5129873 => FieldType {
+                        name: "exec".to_string(),
+                        type_: Type::Boolean,
+                        repeated: false,
+                    },
                 }
             },
 
             // https://docs.docker.com/reference/dockerfile/#from
             29187312 => ObjectType {
                 name: "Dockerfile FROM".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     281731 => FieldType {
                         name: "image".to_string(),
                         type_: Type::String,
@@ -334,7 +503,7 @@ pub fn create_schema() -> Schema {
             // https://docs.docker.com/reference/dockerfile/#run
             273819273 => ObjectType {
                 name: "Dockerfile RUN".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     1279811 => FieldType {
                         name: "command".to_string(),
                         type_: Type::String,
@@ -349,7 +518,7 @@ pub fn create_schema() -> Schema {
             },
             87123897 => ObjectType {
                 name: "Dockerfile RUN network".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     29187312 => FieldType {
                         name: "default".to_string(),
                         type_: Type::String,
@@ -370,7 +539,7 @@ pub fn create_schema() -> Schema {
 
             27092 => ObjectType {
                 name: "User".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     0 => FieldType {
                         name: "name".to_string(),
                         type_: Type::String,
@@ -395,7 +564,7 @@ pub fn create_schema() -> Schema {
             },
             298732 => ObjectType {
                 name: "FlatArticle".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     37438 => FieldType {
                         name: "blocks".to_string(),
                         type_: Type::Object(28398),
@@ -405,7 +574,7 @@ pub fn create_schema() -> Schema {
             },
             28398 => ObjectType {
                 name: "FlatArticleBlock".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     29382 => FieldType {
                         name: "h1".to_string(),
                         type_: Type::String,
@@ -451,7 +620,7 @@ pub fn create_schema() -> Schema {
 
             9823923 => ObjectType {
                 name: "TreeArticle".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     34837 => FieldType {
                         name: "sections".to_string(),
                         type_: Type::Object(8734289),
@@ -462,7 +631,7 @@ pub fn create_schema() -> Schema {
 
             8734289 => ObjectType {
                 name: "TreeArticleSection".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     21837 => FieldType {
                         name: "title".to_string(),
                         type_: Type::String,
@@ -473,12 +642,18 @@ pub fn create_schema() -> Schema {
                         type_: Type::Object(93847373),
                         repeated: true,
                     },
+                    // Subsections, one heading level below this one.
+                    4872361 => FieldType {
+                        name: "sections".to_string(),
+                        type_: Type::Object(8734289),
+                        repeated: true,
+                    },
                 },
             },
 
             93847373 => ObjectType {
                 name: "TreeArticleBlock".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     387439 => FieldType {
                         name: "text".to_string(),
                         type_: Type::String,
@@ -494,12 +669,22 @@ pub fn create_schema() -> Schema {
                         type_: Type::String,
                         repeated: false,
                     },
+                    2938471 => FieldType {
+                        name: "ordered list".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
+                    2938473 => FieldType {
+                        name: "unordered list".to_string(),
+                        type_: Type::String,
+                        repeated: true,
+                    },
                 },
             },
 
             2325 => ObjectType {
                 name: "Post".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     0 => FieldType {
                         name: "title".to_string(),
                         type_: Type::String,
@@ -544,7 +729,7 @@ pub fn create_schema() -> Schema {
             },
             5528 => ObjectType {
                 name: "Comment".to_string(),
-                fields: btreemap! {
+                fields: unique_map! {
                     0 => FieldType {
                         name: "content".to_string(),
                         type_: Type::String,
@@ -560,3 +745,852 @@ pub fn create_schema() -> Schema {
         },
     }
 }
+
+// Schemas are stored as nodes like any other content, conforming to the following meta-schema.
+pub const META_SCHEMA_TYPE_ID: ID = 4180001;
+pub const META_OBJECT_TYPE_TYPE_ID: ID = 4180002;
+pub const META_FIELD_TYPE_TYPE_ID: ID = 4180003;
+
+pub fn meta_schema() -> Schema {
+    Schema {
+        root_object_type_id: META_SCHEMA_TYPE_ID,
+        object_types: hashmap! {
+            META_SCHEMA_TYPE_ID => ObjectType {
+                name: "Schema".to_string(),
+                fields: btreemap! {
+                    0 => FieldType {
+                        name: "root_object_type_id".to_string(),
+                        type_: Type::Int,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "object_types".to_string(),
+                        type_: Type::Object(META_OBJECT_TYPE_TYPE_ID),
+                        repeated: true,
+                    },
+                },
+            },
+            META_OBJECT_TYPE_TYPE_ID => ObjectType {
+                name: "ObjectType".to_string(),
+                fields: btreemap! {
+                    0 => FieldType {
+                        name: "id".to_string(),
+                        type_: Type::Int,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "name".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    2 => FieldType {
+                        name: "fields".to_string(),
+                        type_: Type::Object(META_FIELD_TYPE_TYPE_ID),
+                        repeated: true,
+                    },
+                },
+            },
+            META_FIELD_TYPE_TYPE_ID => ObjectType {
+                name: "FieldType".to_string(),
+                fields: btreemap! {
+                    0 => FieldType {
+                        name: "id".to_string(),
+                        type_: Type::Int,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "name".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    // One of "string", "int", "number", "boolean", "object".
+                    2 => FieldType {
+                        name: "type".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    // Only set for "object" types.
+                    3 => FieldType {
+                        name: "object_type_id".to_string(),
+                        type_: Type::Int,
+                        repeated: false,
+                    },
+                    4 => FieldType {
+                        name: "repeated".to_string(),
+                        type_: Type::Boolean,
+                        repeated: false,
+                    },
+                },
+            },
+        },
+    }
+}
+
+// Schema nodes get a fixed id, so that the same schema always results in the same digests.
+fn add_node(nodes: &mut Vec<Node>, value: Value) -> D {
+    let node = Node {
+        id: 0,
+        value,
+        schema: None,
+    };
+    let digest = node.digest();
+    nodes.push(node);
+    digest
+}
+
+fn add_object(nodes: &mut Vec<Node>, object_type_id: ID, fields: BTreeMap<ID, Vec<D>>) -> D {
+    add_node(
+        nodes,
+        Value::Object(ObjectValue {
+            object_type_id,
+            fields,
+        }),
+    )
+}
+
+// Converts the schema to nodes conforming to the meta-schema. Returns the digest of the root node
+// and all the nodes to store.
+pub fn schema_to_nodes(schema: &Schema) -> (D, Vec<Node>) {
+    let mut nodes = vec![];
+    let mut object_type_ids = schema.object_types.keys().cloned().collect::<Vec<_>>();
+    object_type_ids.sort();
+    let object_type_digests = object_type_ids
+        .iter()
+        .map(|id| object_type_to_nodes(&mut nodes, *id, &schema.object_types[id]))
+        .collect();
+    let root_object_type_id = add_node(&mut nodes, Value::Int(schema.root_object_type_id.into()));
+    let digest = add_object(
+        &mut nodes,
+        META_SCHEMA_TYPE_ID,
+        btreemap! {
+            0 => vec![root_object_type_id],
+            1 => object_type_digests,
+        },
+    );
+    (digest, nodes)
+}
+
+fn object_type_to_nodes(nodes: &mut Vec<Node>, id: ID, object_type: &ObjectType) -> D {
+    let field_digests = object_type
+        .fields
+        .iter()
+        .map(|(field_id, field_type)| field_type_to_nodes(nodes, *field_id, field_type))
+        .collect();
+    let id = add_node(nodes, Value::Int(id.into()));
+    let name = add_node(nodes, Value::String(object_type.name.clone()));
+    add_object(
+        nodes,
+        META_OBJECT_TYPE_TYPE_ID,
+        btreemap! {
+            0 => vec![id],
+            1 => vec![name],
+            2 => field_digests,
+        },
+    )
+}
+
+fn field_type_to_nodes(nodes: &mut Vec<Node>, id: ID, field_type: &FieldType) -> D {
+    let (kind, object_type_id) = match field_type.type_ {
+        Type::String => ("string", None),
+        Type::Int => ("int", None),
+        Type::Number => ("number", None),
+        Type::Boolean => ("boolean", None),
+        Type::Object(object_type_id) => ("object", Some(object_type_id)),
+    };
+    let mut fields = btreemap! {
+        0 => vec![add_node(nodes, Value::Int(id.into()))],
+        1 => vec![add_node(nodes, Value::String(field_type.name.clone()))],
+        2 => vec![add_node(nodes, Value::String(kind.to_string()))],
+        4 => vec![add_node(nodes, Value::Boolean(field_type.repeated))],
+    };
+    if let Some(object_type_id) = object_type_id {
+        fields.insert(3, vec![add_node(nodes, Value::Int(object_type_id.into()))]);
+    }
+    add_object(nodes, META_FIELD_TYPE_TYPE_ID, fields)
+}
+
+// Reads back a schema stored by `schema_to_nodes`. Returns None if any node is missing or does not
+// conform to the meta-schema.
+pub fn schema_from_nodes<G: Fn(&D) -> Option<Node>>(get_node: &G, digest: &D) -> Option<Schema> {
+    let fields = object_fields(get_node, digest, META_SCHEMA_TYPE_ID)?;
+    let root_object_type_id = id_field(get_node, &fields, 0)?;
+    let mut object_types = HashMap::new();
+    for object_type_digest in fields.get(&1).into_iter().flatten() {
+        let fields = object_fields(get_node, object_type_digest, META_OBJECT_TYPE_TYPE_ID)?;
+        let mut object_type = ObjectType {
+            name: string_field(get_node, &fields, 1)?,
+            fields: BTreeMap::new(),
+        };
+        for field_type_digest in fields.get(&2).into_iter().flatten() {
+            let fields = object_fields(get_node, field_type_digest, META_FIELD_TYPE_TYPE_ID)?;
+            let type_ = match string_field(get_node, &fields, 2)?.as_str() {
+                "string" => Type::String,
+                "int" => Type::Int,
+                "number" => Type::Number,
+                "boolean" => Type::Boolean,
+                "object" => Type::Object(id_field(get_node, &fields, 3)?),
+                _ => return None,
+            };
+            let field_type = FieldType {
+                name: string_field(get_node, &fields, 1)?,
+                type_,
+                repeated: bool_field(get_node, &fields, 4).unwrap_or(false),
+            };
+            object_type
+                .fields
+                .insert(id_field(get_node, &fields, 0)?, field_type);
+        }
+        object_types.insert(id_field(get_node, &fields, 0)?, object_type);
+    }
+    Some(Schema {
+        root_object_type_id,
+        object_types,
+    })
+}
+
+fn object_fields<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    digest: &D,
+    object_type_id: ID,
+) -> Option<BTreeMap<ID, Vec<D>>> {
+    match get_node(digest)?.value {
+        Value::Object(object) if object.object_type_id == object_type_id => Some(object.fields),
+        _ => None,
+    }
+}
+
+// Value of the first entry of the given field.
+fn field_value<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    fields: &BTreeMap<ID, Vec<D>>,
+    field_id: ID,
+) -> Option<Value> {
+    get_node(fields.get(&field_id)?.first()?).map(|node| node.value)
+}
+
+fn id_field<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    fields: &BTreeMap<ID, Vec<D>>,
+    field_id: ID,
+) -> Option<ID> {
+    match field_value(get_node, fields, field_id)? {
+        Value::Int(v) => ID::try_from(v).ok(),
+        _ => None,
+    }
+}
+
+fn string_field<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    fields: &BTreeMap<ID, Vec<D>>,
+    field_id: ID,
+) -> Option<String> {
+    match field_value(get_node, fields, field_id)? {
+        Value::String(v) => Some(v),
+        _ => None,
+    }
+}
+
+fn bool_field<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    fields: &BTreeMap<ID, Vec<D>>,
+    field_id: ID,
+) -> Option<bool> {
+    match field_value(get_node, fields, field_id)? {
+        Value::Boolean(v) => Some(v),
+        _ => None,
+    }
+}
+
+// Stores the schema and returns the digest of its root node.
+pub fn store_schema<S: Store<Node, D>>(store: &S, schema: &Schema) -> D {
+    let (digest, nodes) = schema_to_nodes(schema);
+    for node in nodes {
+        store.put(node);
+    }
+    digest
+}
+
+pub fn load_schema<S: Store<Node, D>>(store: &S, digest: &D) -> Option<Schema> {
+    let get_node = |digest: &D| store.get(digest);
+    let schema = schema_from_nodes(&get_node, digest)?;
+    // Reading the schema papers over some problems, e.g. duplicate ids.
+    for error in validate_schema_nodes(&get_node, digest) {
+        logging::warn!("schema {}: {}", digest.to_hex(), error);
+    }
+    Some(schema)
+}
+
+#[test]
+fn test_schema_nodes_round_trip() {
+    for schema in [create_schema(), meta_schema()] {
+        let (digest, nodes) = schema_to_nodes(&schema);
+        let nodes = nodes
+            .into_iter()
+            .map(|node| (node.digest(), node))
+            .collect::<HashMap<_, _>>();
+        let get_node = |d: &D| nodes.get(d).cloned();
+        assert_eq!(schema_from_nodes(&get_node, &digest), Some(schema));
+    }
+}
+
+#[test]
+fn test_schema_nodes_are_deterministic() {
+    // Each call creates maps with a different iteration order.
+    let (digest_1, _) = schema_to_nodes(&create_schema());
+    let (digest_2, _) = schema_to_nodes(&create_schema());
+    assert_eq!(digest_1, digest_2);
+    let (meta_digest, _) = schema_to_nodes(&meta_schema());
+    assert_ne!(digest_1, meta_digest);
+}
+
+#[test]
+fn test_schema_from_nodes_missing_node() {
+    let (digest, nodes) = schema_to_nodes(&create_schema());
+    // Drop one of the leaves.
+    let missing = nodes[0].digest();
+    let nodes = nodes
+        .into_iter()
+        .filter(|node| node.digest() != missing)
+        .map(|node| (node.digest(), node))
+        .collect::<HashMap<_, _>>();
+    let get_node = |d: &D| nodes.get(d).cloned();
+    assert_eq!(schema_from_nodes(&get_node, &digest), None);
+}
+
+// Edits to a schema, as performed by the schema editor.
+#[derive(Clone, Debug)]
+pub enum SchemaAction {
+    AddObjectType {
+        id: ID,
+        name: String,
+    },
+    DeleteObjectType {
+        id: ID,
+    },
+    RenameObjectType {
+        id: ID,
+        name: String,
+    },
+    SetRootObjectType {
+        id: ID,
+    },
+    // Ids are never reused, so that existing values are not read as the new field.
+    AddField {
+        object_type_id: ID,
+        field_id: ID,
+        name: String,
+        type_: Type,
+    },
+    DeleteField {
+        object_type_id: ID,
+        field_id: ID,
+    },
+    RenameField {
+        object_type_id: ID,
+        field_id: ID,
+        name: String,
+    },
+    SetFieldType {
+        object_type_id: ID,
+        field_id: ID,
+        type_: Type,
+    },
+    SetFieldRepeated {
+        object_type_id: ID,
+        field_id: ID,
+        repeated: bool,
+    },
+}
+
+impl Schema {
+    pub fn apply(&mut self, action: SchemaAction) {
+        match action {
+            SchemaAction::AddObjectType { id, name } => {
+                self.object_types.entry(id).or_insert(ObjectType {
+                    name,
+                    fields: BTreeMap::new(),
+                });
+            }
+            SchemaAction::DeleteObjectType { id } => {
+                // The root type is required.
+                if id != self.root_object_type_id {
+                    self.object_types.remove(&id);
+                }
+            }
+            SchemaAction::RenameObjectType { id, name } => {
+                if let Some(object_type) = self.object_types.get_mut(&id) {
+                    object_type.name = name;
+                }
+            }
+            SchemaAction::SetRootObjectType { id } => {
+                if self.object_types.contains_key(&id) {
+                    self.root_object_type_id = id;
+                }
+            }
+            SchemaAction::AddField {
+                object_type_id,
+                field_id,
+                name,
+                type_,
+            } => {
+                if let Some(object_type) = self.object_types.get_mut(&object_type_id) {
+                    object_type.fields.entry(field_id).or_insert(FieldType {
+                        name,
+                        type_,
+                        repeated: false,
+                    });
+                }
+            }
+            SchemaAction::DeleteField {
+                object_type_id,
+                field_id,
+            } => {
+                if let Some(object_type) = self.object_types.get_mut(&object_type_id) {
+                    object_type.fields.remove(&field_id);
+                }
+            }
+            SchemaAction::RenameField {
+                object_type_id,
+                field_id,
+                name,
+            } => {
+                if let Some(field_type) = self.field_type_mut(object_type_id, field_id) {
+                    field_type.name = name;
+                }
+            }
+            SchemaAction::SetFieldType {
+                object_type_id,
+                field_id,
+                type_,
+            } => {
+                if let Some(field_type) = self.field_type_mut(object_type_id, field_id) {
+                    field_type.type_ = type_;
+                }
+            }
+            SchemaAction::SetFieldRepeated {
+                object_type_id,
+                field_id,
+                repeated,
+            } => {
+                if let Some(field_type) = self.field_type_mut(object_type_id, field_id) {
+                    field_type.repeated = repeated;
+                }
+            }
+        }
+    }
+
+    fn field_type_mut(&mut self, object_type_id: ID, field_id: ID) -> Option<&mut FieldType> {
+        self.object_types
+            .get_mut(&object_type_id)?
+            .fields
+            .get_mut(&field_id)
+    }
+}
+
+// Key identifying a type in the schema editor: the name of primitive types, or the id of object
+// types.
+pub fn type_key(type_: &Type) -> String {
+    match type_ {
+        Type::String => "string".to_string(),
+        Type::Int => "int".to_string(),
+        Type::Number => "number".to_string(),
+        Type::Boolean => "boolean".to_string(),
+        Type::Object(object_type_id) => object_type_id.to_string(),
+    }
+}
+
+pub fn parse_type_key(key: &str) -> Option<Type> {
+    match key {
+        "string" => Some(Type::String),
+        "int" => Some(Type::Int),
+        "number" => Some(Type::Number),
+        "boolean" => Some(Type::Boolean),
+        _ => key.parse::<ID>().ok().map(Type::Object),
+    }
+}
+
+#[test]
+fn test_schema_apply() {
+    let mut schema = Schema {
+        root_object_type_id: 1,
+        object_types: hashmap! {
+            1 => ObjectType {
+                name: "Root".to_string(),
+                fields: BTreeMap::new(),
+            },
+        },
+    };
+    schema.apply(SchemaAction::AddObjectType {
+        id: 2,
+        name: "Item".to_string(),
+    });
+    schema.apply(SchemaAction::AddField {
+        object_type_id: 1,
+        field_id: 0,
+        name: "title".to_string(),
+        type_: Type::String,
+    });
+    schema.apply(SchemaAction::AddField {
+        object_type_id: 1,
+        field_id: 1,
+        name: "items".to_string(),
+        type_: Type::String,
+    });
+    schema.apply(SchemaAction::SetFieldType {
+        object_type_id: 1,
+        field_id: 1,
+        type_: Type::Object(2),
+    });
+    schema.apply(SchemaAction::SetFieldRepeated {
+        object_type_id: 1,
+        field_id: 1,
+        repeated: true,
+    });
+    schema.apply(SchemaAction::RenameField {
+        object_type_id: 1,
+        field_id: 0,
+        name: "name".to_string(),
+    });
+    schema.apply(SchemaAction::RenameObjectType {
+        id: 2,
+        name: "Entry".to_string(),
+    });
+    assert_eq!(
+        schema.object_types[&1].fields,
+        btreemap! {
+            0 => FieldType {
+                name: "name".to_string(),
+                type_: Type::String,
+                repeated: false,
+            },
+            1 => FieldType {
+                name: "items".to_string(),
+                type_: Type::Object(2),
+                repeated: true,
+            },
+        }
+    );
+    assert_eq!(schema.object_types[&2].name, "Entry");
+
+    schema.apply(SchemaAction::DeleteField {
+        object_type_id: 1,
+        field_id: 0,
+    });
+    assert_eq!(
+        schema.object_types[&1].fields.keys().collect::<Vec<_>>(),
+        vec![&1]
+    );
+    // The root type cannot be deleted, and only existing types can become the root.
+    schema.apply(SchemaAction::DeleteObjectType { id: 1 });
+    schema.apply(SchemaAction::SetRootObjectType { id: 3 });
+    assert_eq!(schema.root_object_type_id, 1);
+    schema.apply(SchemaAction::SetRootObjectType { id: 2 });
+    schema.apply(SchemaAction::DeleteObjectType { id: 1 });
+    assert_eq!(schema.object_types.keys().collect::<Vec<_>>(), vec![&2]);
+}
+
+#[test]
+fn test_type_key() {
+    for type_ in [
+        Type::String,
+        Type::Int,
+        Type::Number,
+        Type::Boolean,
+        Type::Object(123),
+    ] {
+        assert_eq!(parse_type_key(&type_key(&type_)), Some(type_));
+    }
+    assert_eq!(parse_type_key("unknown"), None);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaError {
+    // The schema nodes are missing or do not conform to the meta-schema.
+    Malformed,
+    MissingRootObjectType {
+        id: ID,
+    },
+    DuplicateObjectTypeId {
+        id: ID,
+    },
+    DuplicateFieldId {
+        object_type_id: ID,
+        field_id: ID,
+    },
+    DuplicateFieldName {
+        object_type_id: ID,
+        name: String,
+    },
+    UnknownObjectType {
+        object_type_id: ID,
+        field_id: ID,
+        type_id: ID,
+    },
+    UnreachableObjectType {
+        id: ID,
+    },
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Malformed => write!(f, "malformed schema"),
+            SchemaError::MissingRootObjectType { id } => {
+                write!(f, "root object type #{} is not defined", id)
+            }
+            SchemaError::DuplicateObjectTypeId { id } => {
+                write!(f, "object type #{} is defined more than once", id)
+            }
+            SchemaError::DuplicateFieldId {
+                object_type_id,
+                field_id,
+            } => write!(
+                f,
+                "field #{} of object type #{} is defined more than once",
+                field_id, object_type_id
+            ),
+            SchemaError::DuplicateFieldName {
+                object_type_id,
+                name,
+            } => write!(
+                f,
+                "field name {:?} is used more than once in object type #{}",
+                name, object_type_id
+            ),
+            SchemaError::UnknownObjectType {
+                object_type_id,
+                field_id,
+                type_id,
+            } => write!(
+                f,
+                "field #{} of object type #{} refers to undefined object type #{}",
+                field_id, object_type_id, type_id
+            ),
+            SchemaError::UnreachableObjectType { id } => {
+                write!(f, "object type #{} is not reachable from the root", id)
+            }
+        }
+    }
+}
+
+impl Schema {
+    // Duplicate ids cannot be represented in a `Schema`; they are detected when reading schema
+    // nodes instead, see `validate_schema_nodes`.
+    pub fn validate(&self) -> Vec<SchemaError> {
+        let mut errors = vec![];
+        if !self.object_types.contains_key(&self.root_object_type_id) {
+            errors.push(SchemaError::MissingRootObjectType {
+                id: self.root_object_type_id,
+            });
+        }
+        let mut object_type_ids = self.object_types.keys().cloned().collect::<Vec<_>>();
+        object_type_ids.sort();
+        for object_type_id in object_type_ids.iter().cloned() {
+            let mut names = HashSet::new();
+            for (field_id, field_type) in self.object_types[&object_type_id].fields.iter() {
+                if !names.insert(field_type.name.as_str()) {
+                    errors.push(SchemaError::DuplicateFieldName {
+                        object_type_id,
+                        name: field_type.name.clone(),
+                    });
+                }
+                if let Type::Object(type_id) = field_type.type_ {
+                    if !self.object_types.contains_key(&type_id) {
+                        errors.push(SchemaError::UnknownObjectType {
+                            object_type_id,
+                            field_id: *field_id,
+                            type_id,
+                        });
+                    }
+                }
+            }
+        }
+        let reachable = self.reachable_object_types();
+        for id in object_type_ids {
+            if !reachable.contains(&id) {
+                errors.push(SchemaError::UnreachableObjectType { id });
+            }
+        }
+        errors
+    }
+
+    fn reachable_object_types(&self) -> HashSet<ID> {
+        let mut reachable = HashSet::new();
+        let mut queue = vec![self.root_object_type_id];
+        while let Some(id) = queue.pop() {
+            let Some(object_type) = self.object_types.get(&id) else {
+                continue;
+            };
+            if !reachable.insert(id) {
+                continue;
+            }
+            for field_type in object_type.fields.values() {
+                if let Type::Object(type_id) = field_type.type_ {
+                    queue.push(type_id);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+// Validates a schema stored as nodes, including the duplicate ids that are lost when reading it
+// back as a `Schema`.
+pub fn validate_schema_nodes<G: Fn(&D) -> Option<Node>>(
+    get_node: &G,
+    digest: &D,
+) -> Vec<SchemaError> {
+    let Some(schema) = schema_from_nodes(get_node, digest) else {
+        return vec![SchemaError::Malformed];
+    };
+    if !validate_document(&meta_schema(), get_node, digest).is_empty() {
+        return vec![SchemaError::Malformed];
+    }
+    let mut errors = vec![];
+    let fields = object_fields(get_node, digest, META_SCHEMA_TYPE_ID).unwrap_or_default();
+    let mut object_type_ids = HashSet::new();
+    for object_type_digest in fields.get(&1).into_iter().flatten() {
+        let fields = object_fields(get_node, object_type_digest, META_OBJECT_TYPE_TYPE_ID)
+            .unwrap_or_default();
+        let Some(object_type_id) = id_field(get_node, &fields, 0) else {
+            continue;
+        };
+        if !object_type_ids.insert(object_type_id) {
+            errors.push(SchemaError::DuplicateObjectTypeId { id: object_type_id });
+        }
+        let mut field_ids = HashSet::new();
+        for field_type_digest in fields.get(&2).into_iter().flatten() {
+            let field_id = object_fields(get_node, field_type_digest, META_FIELD_TYPE_TYPE_ID)
+                .and_then(|fields| id_field(get_node, &fields, 0));
+            if let Some(field_id) = field_id {
+                if !field_ids.insert(field_id) {
+                    errors.push(SchemaError::DuplicateFieldId {
+                        object_type_id,
+                        field_id,
+                    });
+                }
+            }
+        }
+    }
+    errors.extend(schema.validate());
+    errors
+}
+
+#[test]
+fn test_shipped_schemas_are_valid() {
+    assert_eq!(create_schema().validate(), vec![]);
+    assert_eq!(meta_schema().validate(), vec![]);
+}
+
+#[test]
+fn test_schema_validate() {
+    let schema = Schema {
+        root_object_type_id: 1,
+        object_types: hashmap! {
+            1 => ObjectType {
+                name: "Root".to_string(),
+                fields: btreemap! {
+                    0 => FieldType {
+                        name: "a".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "a".to_string(),
+                        type_: Type::Object(3),
+                        repeated: false,
+                    },
+                },
+            },
+            2 => ObjectType {
+                name: "Orphan".to_string(),
+                fields: btreemap! {},
+            },
+        },
+    };
+    assert_eq!(
+        schema.validate(),
+        vec![
+            SchemaError::DuplicateFieldName {
+                object_type_id: 1,
+                name: "a".to_string(),
+            },
+            SchemaError::UnknownObjectType {
+                object_type_id: 1,
+                field_id: 1,
+                type_id: 3,
+            },
+            SchemaError::UnreachableObjectType { id: 2 },
+        ]
+    );
+    let schema = Schema {
+        root_object_type_id: 1,
+        object_types: hashmap! {},
+    };
+    assert_eq!(
+        schema.validate(),
+        vec![SchemaError::MissingRootObjectType { id: 1 }]
+    );
+}
+
+#[test]
+fn test_validate_schema_nodes_duplicate_ids() {
+    let mut nodes = vec![];
+    let field_type = FieldType {
+        name: "a".to_string(),
+        type_: Type::String,
+        repeated: false,
+    };
+    let field_1 = field_type_to_nodes(&mut nodes, 0, &field_type);
+    let field_2 = field_type_to_nodes(
+        &mut nodes,
+        0,
+        &FieldType {
+            name: "b".to_string(),
+            ..field_type
+        },
+    );
+    let object_type_id = add_node(&mut nodes, Value::Int(1));
+    let name = add_node(&mut nodes, Value::String("Root".to_string()));
+    let object_type = add_object(
+        &mut nodes,
+        META_OBJECT_TYPE_TYPE_ID,
+        btreemap! {
+            0 => vec![object_type_id.clone()],
+            1 => vec![name],
+            2 => vec![field_1, field_2],
+        },
+    );
+    let digest = add_object(
+        &mut nodes,
+        META_SCHEMA_TYPE_ID,
+        btreemap! {
+            0 => vec![object_type_id],
+            1 => vec![object_type.clone(), object_type],
+        },
+    );
+    let nodes = nodes
+        .into_iter()
+        .map(|node| (node.digest(), node))
+        .collect::<HashMap<_, _>>();
+    let get_node = |d: &D| nodes.get(d).cloned();
+    assert_eq!(
+        validate_schema_nodes(&get_node, &digest),
+        vec![
+            SchemaError::DuplicateFieldId {
+                object_type_id: 1,
+                field_id: 0,
+            },
+            // The object type is listed twice.
+            SchemaError::DuplicateObjectTypeId { id: 1 },
+            SchemaError::DuplicateFieldId {
+                object_type_id: 1,
+                field_id: 0,
+            },
+        ]
+    );
+    assert_eq!(
+        validate_schema_nodes(&get_node, &D::default()),
+        vec![SchemaError::Malformed]
+    );
+}
@@ -1,83 +1,647 @@
 use leptos::*;
-use std::{collections::HashMap, hash};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash,
+    rc::Rc,
+};
 
-use leptos::{Signal, SignalGetUntracked};
+use leptos::Signal;
 use leptos_use::{
     storage::use_local_storage,
     utils::FromToStringCodec,
 };
 use maplit::hashmap;
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode, Storage};
 
 use crate::{HasDigest, Node, D};
 
+// Stores are shared, e.g. through context, so writing only needs a shared reference.
 pub trait Store<T, D>
 where
     T: HasDigest<Digest = D>,
 {
-    fn get(&self, digest: &D) -> Option<&T>;
+    fn get(&self, digest: &D) -> Option<T>;
     fn has(&self, digest: &D) -> bool;
-    fn put(&mut self, value: T) -> D;
+    fn put(&self, value: T) -> D;
+    // Digests of all the values in the store.
+    fn digests(&self) -> Vec<D>;
+    fn remove(&self, digest: &D);
+}
+
+// Like `Store`, for stores that are only available asynchronously.
+pub trait AsyncStore<T, D>
+where
+    T: HasDigest<Digest = D>,
+{
+    async fn get(&self, digest: &D) -> anyhow::Result<Option<T>>;
+    async fn has(&self, digest: &D) -> anyhow::Result<bool>;
+    async fn put(&self, value: T) -> anyhow::Result<D>;
 }
 
-pub struct LocalStorage<T, D>
+// Keeps values in memory only, e.g. for tests.
+pub struct MemoryStore<T, D>
 where
     T: HasDigest<Digest = D>,
 {
-    storage: HashMap<D, T>,
+    storage: RefCell<HashMap<D, T>>,
 }
 
-impl<T, D> LocalStorage<T, D>
+impl<T, D> MemoryStore<T, D>
 where
     T: HasDigest<Digest = D>,
 {
     pub fn new() -> Self {
-        LocalStorage {
-            storage: hashmap! {},
+        MemoryStore {
+            storage: RefCell::new(hashmap! {}),
         }
     }
 }
 
-impl<T, D> Store<T, D> for LocalStorage<T, D>
+impl<T, D> Store<T, D> for MemoryStore<T, D>
 where
-    T: HasDigest<Digest = D>,
+    T: HasDigest<Digest = D> + Clone,
     D: Eq + hash::Hash + Clone,
 {
-    fn get(&self, digest: &D) -> Option<&T> {
-        self.storage.get(digest)
+    fn get(&self, digest: &D) -> Option<T> {
+        self.storage.borrow().get(digest).cloned()
+    }
+
+    fn has(&self, digest: &D) -> bool {
+        self.storage.borrow().contains_key(digest)
+    }
+
+    fn put(&self, value: T) -> D {
+        let digest = value.digest();
+        self.storage.borrow_mut().insert(digest.clone(), value);
+        digest
+    }
+
+    fn digests(&self) -> Vec<D> {
+        self.storage.borrow().keys().cloned().collect()
+    }
+
+    fn remove(&self, digest: &D) {
+        self.storage.borrow_mut().remove(digest);
+    }
+}
+
+impl<T, D> AsyncStore<T, D> for MemoryStore<T, D>
+where
+    T: HasDigest<Digest = D> + Clone,
+    D: Eq + hash::Hash + Clone,
+{
+    async fn get(&self, digest: &D) -> anyhow::Result<Option<T>> {
+        Ok(Store::get(self, digest))
+    }
+
+    async fn has(&self, digest: &D) -> anyhow::Result<bool> {
+        Ok(Store::has(self, digest))
+    }
+
+    async fn put(&self, value: T) -> anyhow::Result<D> {
+        Ok(Store::put(self, value))
+    }
+}
+
+const DATABASE_NAME: &str = "lint";
+const DATABASE_VERSION: u32 = 1;
+const OBJECT_STORE_NAME: &str = "nodes";
+
+// Values stored in IndexedDB, as JSON keyed by the hex digest. Only the keys are read up front;
+// values are read from the database on demand, through `AsyncStore` or `load`.
+pub struct IndexedDbStore<T> {
+    db: IdbDatabase,
+    // Digests of everything in the database, so that `Store` can tell what is stored without
+    // waiting.
+    keys: Rc<RefCell<HashSet<D>>>,
+    // Values read or written so far, which `Store` reads from.
+    cache: Rc<RefCell<HashMap<D, T>>>,
+}
+
+impl<T> IndexedDbStore<T>
+where
+    T: HasDigest<Digest = D> + Serialize + DeserializeOwned,
+{
+    pub async fn open() -> anyhow::Result<Self> {
+        let factory = window()
+            .indexed_db()
+            .map_err(js_error)?
+            .ok_or_else(|| anyhow::anyhow!("IndexedDB is not available"))?;
+        let request = factory
+            .open_with_u32(DATABASE_NAME, DATABASE_VERSION)
+            .map_err(js_error)?;
+        let upgrade_request = request.clone();
+        let on_upgrade_needed = wasm_bindgen::closure::Closure::<dyn FnMut()>::new(move || {
+            let db = upgrade_request
+                .result()
+                .expect("no database on upgrade")
+                .unchecked_into::<IdbDatabase>();
+            if !db.object_store_names().contains(OBJECT_STORE_NAME) {
+                db.create_object_store(OBJECT_STORE_NAME)
+                    .expect("failed to create object store");
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade_needed.as_ref().unchecked_ref()));
+        let db = request_result(&request)
+            .await?
+            .unchecked_into::<IdbDatabase>();
+        request.set_onupgradeneeded(None);
+
+        let get_all_keys = object_store(&db, IdbTransactionMode::Readonly)?
+            .get_all_keys()
+            .map_err(js_error)?;
+        let all_keys = request_result(&get_all_keys).await?;
+        let mut keys = HashSet::new();
+        for key in js_sys::Array::from(&all_keys).iter() {
+            match key.as_string().and_then(|hex| D::parse_hex(&hex)) {
+                Some(digest) => {
+                    keys.insert(digest);
+                }
+                None => logging::warn!("skipping unreadable key {:?}", key),
+            }
+        }
+        Ok(IndexedDbStore {
+            db,
+            keys: Rc::new(RefCell::new(keys)),
+            cache: Rc::new(RefCell::new(HashMap::new())),
+        })
+    }
+
+    // Reads the value from the database into the cache, unless it is cached already. The read is
+    // started right away, so the returned future does not borrow the store.
+    pub fn load(&self, digest: &D) -> impl Future<Output = anyhow::Result<Option<T>>> + 'static
+    where
+        T: Clone + 'static,
+    {
+        let cached = self.cache.borrow().get(digest).cloned();
+        let request = (cached.is_none() && self.keys.borrow().contains(digest)).then(|| {
+            object_store(&self.db, IdbTransactionMode::Readonly).and_then(|store| {
+                store
+                    .get(&JsValue::from_str(&digest.to_hex()))
+                    .map_err(js_error)
+            })
+        });
+        let cache = self.cache.clone();
+        let digest = digest.clone();
+        async move {
+            let Some(request) = request else {
+                return Ok(cached);
+            };
+            let Some(json) = request_result(&request?).await?.as_string() else {
+                return Ok(None);
+            };
+            let value = serde_json::from_str::<T>(&json)?;
+            cache.borrow_mut().insert(digest, value.clone());
+            Ok(Some(value))
+        }
+    }
+
+    // Starts writing the value to the database, without waiting for it to be written.
+    fn write(&self, digest: &D, value: &T) -> anyhow::Result<IdbRequest> {
+        let json = serde_json::to_string(value)?;
+        object_store(&self.db, IdbTransactionMode::Readwrite)?
+            .put_with_key(
+                &JsValue::from_str(&json),
+                &JsValue::from_str(&digest.to_hex()),
+            )
+            .map_err(js_error)
+    }
+}
+
+impl<T> Store<T, D> for IndexedDbStore<T>
+where
+    T: HasDigest<Digest = D> + Serialize + DeserializeOwned + Clone + 'static,
+{
+    fn get(&self, digest: &D) -> Option<T> {
+        self.cache.borrow().get(digest).cloned()
     }
 
     fn has(&self, digest: &D) -> bool {
-        self.storage.contains_key(digest)
+        self.keys.borrow().contains(digest)
     }
 
-    fn put(&mut self, value: T) -> D {
+    fn put(&self, value: T) -> D {
         let digest = value.digest();
-        self.storage.insert(digest.clone(), value);
-        let (_state, set_state, _) = use_local_storage::<String, FromToStringCodec>("cc");
-        set_state("xxx".to_string());
+        if !Store::has(self, &digest) {
+            match self.write(&digest, &value) {
+                Ok(request) => {
+                    // The value is read from the cache until it is written, and dropped from it
+                    // if writing fails, so that it is not taken to be stored.
+                    let keys = self.keys.clone();
+                    let cache = self.cache.clone();
+                    let written_digest = digest.clone();
+                    spawn_local(async move {
+                        if let Err(err) = request_result(&request).await {
+                            logging::error!("failed to store {}: {}", written_digest.to_hex(), err);
+                            keys.borrow_mut().remove(&written_digest);
+                            cache.borrow_mut().remove(&written_digest);
+                        }
+                    });
+                }
+                Err(err) => logging::error!("failed to store {}: {}", digest.to_hex(), err),
+            }
+            self.keys.borrow_mut().insert(digest.clone());
+            self.cache.borrow_mut().insert(digest.clone(), value);
+        }
+        digest
+    }
+
+    fn digests(&self) -> Vec<D> {
+        self.keys.borrow().iter().cloned().collect()
+    }
+
+    fn remove(&self, digest: &D) {
+        let deleted = object_store(&self.db, IdbTransactionMode::Readwrite).and_then(|store| {
+            store
+                .delete(&JsValue::from_str(&digest.to_hex()))
+                .map_err(js_error)
+        });
+        if let Err(err) = deleted {
+            logging::error!("failed to delete {}: {}", digest.to_hex(), err);
+        }
+        self.keys.borrow_mut().remove(digest);
+        self.cache.borrow_mut().remove(digest);
+    }
+}
+
+impl<T> AsyncStore<T, D> for IndexedDbStore<T>
+where
+    T: HasDigest<Digest = D> + Serialize + DeserializeOwned + Clone + 'static,
+{
+    // Unlike `Store::get`, reads values that are not cached from the database.
+    async fn get(&self, digest: &D) -> anyhow::Result<Option<T>> {
+        self.load(digest).await
+    }
+
+    async fn has(&self, digest: &D) -> anyhow::Result<bool> {
+        Ok(Store::has(self, digest))
+    }
+
+    // Unlike `Store::put`, waits for the value to be written.
+    async fn put(&self, value: T) -> anyhow::Result<D> {
+        let digest = value.digest();
+        if !Store::has(self, &digest) {
+            request_result(&self.write(&digest, &value)?).await?;
+            self.keys.borrow_mut().insert(digest.clone());
+            self.cache.borrow_mut().insert(digest.clone(), value);
+        }
+        Ok(digest)
+    }
+}
+
+fn object_store(db: &IdbDatabase, mode: IdbTransactionMode) -> anyhow::Result<IdbObjectStore> {
+    db.transaction_with_str_and_mode(OBJECT_STORE_NAME, mode)
+        .and_then(|transaction| transaction.object_store(OBJECT_STORE_NAME))
+        .map_err(js_error)
+}
+
+// Waits for the request to complete and returns its result.
+async fn request_result(request: &IdbRequest) -> anyhow::Result<JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    match outcome {
+        Ok(_) => request.result().map_err(js_error),
+        Err(_) => Err(match request.error() {
+            Ok(Some(err)) => anyhow::anyhow!("{}: {}", err.name(), err.message()),
+            _ => anyhow::anyhow!("request failed"),
+        }),
+    }
+}
+
+fn js_error(err: JsValue) -> anyhow::Error {
+    anyhow::anyhow!("{:?}", err)
+}
+
+// Prefix of the keys that nodes used to be stored under in localStorage.
+const LOCAL_STORAGE_NODE_PREFIX: &str = "sha2-256:";
+
+// Nodes in localStorage, keyed by the hex digest as earlier versions stored them, for when
+// IndexedDB is not available.
+pub struct LocalStorageStore {
+    storage: Storage,
+}
+
+impl LocalStorageStore {
+    pub fn open() -> anyhow::Result<Self> {
+        let storage = window()
+            .local_storage()
+            .map_err(js_error)?
+            .ok_or_else(|| anyhow::anyhow!("localStorage is not available"))?;
+        Ok(LocalStorageStore { storage })
+    }
+}
+
+impl Store<Node, D> for LocalStorageStore {
+    fn get(&self, digest: &D) -> Option<Node> {
+        self.storage
+            .get_item(&digest.to_hex())
+            .ok()
+            .flatten()
+            .and_then(|json| Node::deserialize(&json))
+    }
+
+    fn has(&self, digest: &D) -> bool {
+        Store::get(self, digest).is_some()
+    }
+
+    fn put(&self, node: Node) -> D {
+        let digest = node.digest();
+        if let Err(err) = self
+            .storage
+            .set_item(&digest.to_hex(), &node.serialize())
+            .map_err(js_error)
+        {
+            logging::error!("failed to store {}: {}", digest.to_hex(), err);
+        }
+        digest
+    }
+
+    fn digests(&self) -> Vec<D> {
+        let length = self.storage.length().unwrap_or_default();
+        (0..length)
+            .filter_map(|index| self.storage.key(index).ok().flatten())
+            .filter(|key| key.starts_with(LOCAL_STORAGE_NODE_PREFIX))
+            .filter_map(|key| D::parse_hex(&key))
+            .collect()
+    }
+
+    fn remove(&self, digest: &D) {
+        if let Err(err) = self.storage.remove_item(&digest.to_hex()).map_err(js_error) {
+            logging::error!("failed to delete {}: {}", digest.to_hex(), err);
+        }
+    }
+}
+
+// Moves the nodes stored in localStorage by earlier versions into the store. Keys are only removed
+// once their node is stored, so an interrupted migration is resumed on the next start. Returns
+// the number of nodes moved.
+pub async fn migrate_local_storage<S: AsyncStore<Node, D>>(store: &S) -> anyhow::Result<usize> {
+    let Some(local_storage) = window().local_storage().map_err(js_error)? else {
+        return Ok(0);
+    };
+    let length = local_storage.length().map_err(js_error)?;
+    let keys = (0..length)
+        .filter_map(|index| local_storage.key(index).ok().flatten())
+        .filter(|key| key.starts_with(LOCAL_STORAGE_NODE_PREFIX))
+        .collect::<Vec<_>>();
+    let mut migrated = 0;
+    for key in keys {
+        let node = local_storage
+            .get_item(&key)
+            .map_err(js_error)?
+            .and_then(|json| Node::deserialize(&json));
+        match node {
+            // Entries that do not match their key were never reachable, and are dropped.
+            Some(node) if node.digest().to_hex() == key => {
+                store.put(node).await?;
+                migrated += 1;
+            }
+            _ => logging::warn!("dropping invalid node {}", key),
+        }
+        local_storage.remove_item(&key).map_err(js_error)?;
+    }
+    Ok(migrated)
+}
+
+// Stores the app can keep its nodes in.
+pub enum Backend {
+    IndexedDb(IndexedDbStore<Node>),
+    LocalStorage(LocalStorageStore),
+    // Nodes are lost on reload, for when neither IndexedDB nor localStorage is available.
+    Memory(MemoryStore<Node, D>),
+}
+
+impl Backend {
+    // Reads the node into memory if the store only reads it on demand, see
+    // `IndexedDbStore::load`. The other stores can be read from right away.
+    fn load(&self, digest: &D) -> impl Future<Output = anyhow::Result<Option<Node>>> + 'static {
+        let pending = match self {
+            Backend::IndexedDb(store) => Some(store.load(digest)),
+            Backend::LocalStorage(_) | Backend::Memory(_) => None,
+        };
+        let node = Store::get(self, digest);
+        async move {
+            match pending {
+                Some(pending) => pending.await,
+                None => Ok(node),
+            }
+        }
+    }
+}
+
+impl Store<Node, D> for Backend {
+    fn get(&self, digest: &D) -> Option<Node> {
+        match self {
+            Backend::IndexedDb(store) => Store::get(store, digest),
+            Backend::LocalStorage(store) => Store::get(store, digest),
+            Backend::Memory(store) => Store::get(store, digest),
+        }
+    }
+
+    fn has(&self, digest: &D) -> bool {
+        match self {
+            Backend::IndexedDb(store) => Store::has(store, digest),
+            Backend::LocalStorage(store) => Store::has(store, digest),
+            Backend::Memory(store) => Store::has(store, digest),
+        }
+    }
+
+    fn put(&self, node: Node) -> D {
+        match self {
+            Backend::IndexedDb(store) => Store::put(store, node),
+            Backend::LocalStorage(store) => Store::put(store, node),
+            Backend::Memory(store) => Store::put(store, node),
+        }
+    }
+
+    fn digests(&self) -> Vec<D> {
+        match self {
+            Backend::IndexedDb(store) => store.digests(),
+            Backend::LocalStorage(store) => store.digests(),
+            Backend::Memory(store) => store.digests(),
+        }
+    }
+
+    fn remove(&self, digest: &D) {
+        match self {
+            Backend::IndexedDb(store) => store.remove(digest),
+            Backend::LocalStorage(store) => store.remove(digest),
+            Backend::Memory(store) => store.remove(digest),
+        }
+    }
+}
+
+// The store of the app, provided as context. Copies refer to the same store.
+#[derive(Clone, Copy)]
+pub struct NodeStore {
+    store: StoredValue<Backend>,
+    // Notified when a node is added or loaded, for readers waiting on a missing node.
+    added: Trigger,
+    // Nodes being loaded for `get_signal`, so that each is only read once.
+    loading: StoredValue<HashSet<D>>,
+}
+
+// Besides `get_signal`, the methods of `Store`, so that they can be called without ambiguity with
+// `AsyncStore`.
+impl NodeStore {
+    pub fn new(backend: Backend) -> Self {
+        NodeStore {
+            store: store_value(backend),
+            added: create_trigger(),
+            loading: store_value(HashSet::new()),
+        }
+    }
+
+    pub fn get(&self, digest: &D) -> Option<Node> {
+        self.store.with_value(|store| store.get(digest))
+    }
+
+    // Reads the node, and again once it is added if it is missing. Nodes that are stored but not
+    // loaded yet are loaded in the background.
+    pub fn get_signal(&self, digest: &D) -> Signal<Option<Node>> {
+        let store = *self;
+        let digest = digest.clone();
+        Signal::derive(move || {
+            // Nodes never change once stored, so only a missing node needs to be waited for.
+            store.get(&digest).or_else(|| {
+                store.added.track();
+                if store.has(&digest) {
+                    store.load_in_background(&digest);
+                }
+                store.get(&digest)
+            })
+        })
+    }
+
+    fn load_in_background(&self, digest: &D) {
+        let is_new = self
+            .loading
+            .try_update_value(|loading| loading.insert(digest.clone()))
+            .unwrap_or(false);
+        if !is_new {
+            return;
+        }
+        let store = *self;
+        let digest = digest.clone();
+        spawn_local(async move {
+            match store.load(&digest).await {
+                Ok(Some(_)) => store.added.notify(),
+                Ok(None) => logging::warn!("node {} is missing", digest.to_hex()),
+                Err(err) => logging::error!("failed to load {}: {}", digest.to_hex(), err),
+            }
+            store.loading.update_value(|loading| {
+                loading.remove(&digest);
+            });
+        });
+    }
+
+    // Like `get`, but reads the node from the backend if it is not loaded yet.
+    pub async fn load(&self, digest: &D) -> anyhow::Result<Option<Node>> {
+        self.store.with_value(|store| store.load(digest)).await
+    }
+
+    // Loads every stored node, for readers that need all of them at once, such as garbage
+    // collection.
+    pub async fn load_all(&self) -> anyhow::Result<()> {
+        for digest in Store::digests(self) {
+            self.load(&digest).await?;
+        }
+        Ok(())
+    }
+
+    pub fn has(&self, digest: &D) -> bool {
+        self.store.with_value(|store| store.has(digest))
+    }
+
+    pub fn put(&self, node: Node) -> D {
+        let is_new = !self.has(&node.digest());
+        let digest = self.store.with_value(|store| store.put(node));
+        // Outside of the store, since readers run right away.
+        if is_new {
+            self.added.notify();
+        }
         digest
     }
 }
 
-pub fn get_item(digest: &D) -> Signal<Option<Node>> {
-    let (item, _set_item, _) = use_local_storage::<String, FromToStringCodec>(digest.to_hex());
-    Signal::derive(move || {
-        let item = item();
-        Node::deserialize(&item)
-    })
+impl Store<Node, D> for NodeStore {
+    fn get(&self, digest: &D) -> Option<Node> {
+        NodeStore::get(self, digest)
+    }
+
+    fn has(&self, digest: &D) -> bool {
+        NodeStore::has(self, digest)
+    }
+
+    fn put(&self, node: Node) -> D {
+        NodeStore::put(self, node)
+    }
+
+    fn digests(&self) -> Vec<D> {
+        self.store.with_value(|store| store.digests())
+    }
+
+    fn remove(&self, digest: &D) {
+        self.store.with_value(|store| store.remove(digest))
+    }
 }
 
-pub fn get_item_untracked(digest: &D) -> Option<Node> {
-    let (item, _set_item, _) = use_local_storage::<String, FromToStringCodec>(digest.to_hex());
-    Node::deserialize(&item.get_untracked())
+impl AsyncStore<Node, D> for NodeStore {
+    async fn get(&self, digest: &D) -> anyhow::Result<Option<Node>> {
+        self.load(digest).await
+    }
+
+    async fn has(&self, digest: &D) -> anyhow::Result<bool> {
+        Ok(NodeStore::has(self, digest))
+    }
+
+    async fn put(&self, node: Node) -> anyhow::Result<D> {
+        Ok(NodeStore::put(self, node))
+    }
+}
+
+pub fn use_node_store() -> NodeStore {
+    expect_context::<NodeStore>()
+}
+
+// Opens the store in IndexedDB, moving over any nodes left in localStorage.
+pub async fn open_indexed_db_backend() -> anyhow::Result<Backend> {
+    let store = IndexedDbStore::open().await?;
+    // Nodes that could not be moved stay in localStorage, for the next start.
+    match migrate_local_storage(&store).await {
+        Ok(0) => {}
+        Ok(migrated) => logging::log!("moved {} nodes from localStorage to IndexedDB", migrated),
+        Err(err) => logging::error!("failed to move nodes out of localStorage: {}", err),
+    }
+    Ok(Backend::IndexedDb(store))
 }
 
-pub fn set_item(node: &Node) -> D {
-    let digest = node.digest();
-    let (_item, set_item, _) = use_local_storage::<String, FromToStringCodec>(digest.to_hex());
-    let serialized_node = node.serialize();
-    set_item(serialized_node);
-    digest
+// Opens the best store available: IndexedDB, then localStorage, then memory.
+pub async fn open_backend() -> Backend {
+    let err = match open_indexed_db_backend().await {
+        Ok(backend) => return backend,
+        Err(err) => err,
+    };
+    logging::error!(
+        "failed to open IndexedDB, storing nodes in localStorage: {}",
+        err
+    );
+    LocalStorageStore::open()
+        .map(Backend::LocalStorage)
+        .unwrap_or_else(|err| {
+            logging::error!("failed to open storage, changes will not be saved: {}", err);
+            Backend::Memory(MemoryStore::new())
+        })
 }
 
 pub fn set_root(d: &D) {
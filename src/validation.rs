@@ -0,0 +1,254 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{Node, Path, Schema, Selector, Type, Value, D, ID};
+
+// A way in which a document does not conform to its schema.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    MissingNode { digest: D },
+    UnknownObjectType { object_type_id: ID },
+    // The object has entries for a field that its type does not define.
+    UnknownField { field_id: ID },
+    // A field that is not repeated has more than one entry.
+    TooManyValues { field_id: ID, count: usize },
+    TypeMismatch { expected: Type, found: Option<Type> },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingNode { digest } => write!(f, "node {} not found", digest.to_hex()),
+            Violation::UnknownObjectType { object_type_id } => {
+                write!(
+                    f,
+                    "object type #{} is not defined in the schema",
+                    object_type_id
+                )
+            }
+            Violation::UnknownField { field_id } => {
+                write!(f, "field #{} is not defined in the object type", field_id)
+            }
+            Violation::TooManyValues { field_id, count } => write!(
+                f,
+                "field #{} is not repeated but has {} values",
+                field_id, count
+            ),
+            Violation::TypeMismatch { expected, found } => match found {
+                Some(found) => write!(f, "expected {}, found {}", expected, found),
+                None => write!(f, "expected {}, found empty value", expected),
+            },
+        }
+    }
+}
+
+// Checks the document rooted at the given digest against the schema. Violations are keyed by the
+// path of the offending node, relative to the root.
+pub fn validate_document<G: Fn(&D) -> Option<Node>>(
+    schema: &Schema,
+    get_node: &G,
+    root_digest: &D,
+) -> Vec<(Path, Violation)> {
+    let mut violations = vec![];
+    validate_node(
+        schema,
+        get_node,
+        root_digest,
+        &Type::Object(schema.root_object_type_id),
+        &Path::default(),
+        &mut violations,
+    );
+    violations
+}
+
+fn validate_node<G: Fn(&D) -> Option<Node>>(
+    schema: &Schema,
+    get_node: &G,
+    digest: &D,
+    expected_type: &Type,
+    path: &Path,
+    violations: &mut Vec<(Path, Violation)>,
+) {
+    let Some(node) = get_node(digest) else {
+        violations.push((
+            path.clone(),
+            Violation::MissingNode {
+                digest: digest.clone(),
+            },
+        ));
+        return;
+    };
+    let found = node.value.type_();
+    if found.as_ref() != Some(expected_type) {
+        violations.push((
+            path.clone(),
+            Violation::TypeMismatch {
+                expected: expected_type.clone(),
+                found,
+            },
+        ));
+    }
+    // Even if it is not the expected one, check the object against its own type, so that the
+    // violations further down are reported too.
+    let Value::Object(object) = node.value else {
+        return;
+    };
+    let Some(object_type) = schema.object_types.get(&object.object_type_id) else {
+        violations.push((
+            path.clone(),
+            Violation::UnknownObjectType {
+                object_type_id: object.object_type_id,
+            },
+        ));
+        return;
+    };
+    for (field_id, digests) in object.fields.iter() {
+        let Some(field_type) = object_type.fields.get(field_id) else {
+            violations.push((
+                path.clone(),
+                Violation::UnknownField {
+                    field_id: *field_id,
+                },
+            ));
+            continue;
+        };
+        if !field_type.repeated && digests.len() > 1 {
+            violations.push((
+                path.clone(),
+                Violation::TooManyValues {
+                    field_id: *field_id,
+                    count: digests.len(),
+                },
+            ));
+        }
+        for (index, child_digest) in digests.iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(Selector {
+                field_id: *field_id,
+                index,
+            });
+            validate_node(
+                schema,
+                get_node,
+                child_digest,
+                &field_type.type_,
+                &child_path,
+                violations,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    test_node, test_object_node, test_string_node, FieldType, MemoryStore, ObjectType, Store,
+};
+#[cfg(test)]
+use maplit::{btreemap, hashmap};
+
+#[cfg(test)]
+fn test_schema() -> Schema {
+    Schema {
+        root_object_type_id: 1,
+        object_types: hashmap! {
+            1 => ObjectType {
+                name: "Root".to_string(),
+                fields: btreemap! {
+                    0 => FieldType {
+                        name: "title".to_string(),
+                        type_: Type::String,
+                        repeated: false,
+                    },
+                    1 => FieldType {
+                        name: "children".to_string(),
+                        type_: Type::Object(2),
+                        repeated: true,
+                    },
+                },
+            },
+            2 => ObjectType {
+                name: "Child".to_string(),
+                fields: btreemap! {
+                    0 => FieldType {
+                        name: "count".to_string(),
+                        type_: Type::Int,
+                        repeated: false,
+                    },
+                },
+            },
+        },
+    }
+}
+
+#[test]
+fn test_validate_valid_document() {
+    let store = MemoryStore::new();
+    let title = store.put(test_string_node(0, "title"));
+    let count = store.put(test_node(0, Value::Int(1)));
+    let child = store.put(test_object_node(0, 2, btreemap! { 0 => vec![count] }));
+    let root = store.put(test_object_node(
+        0,
+        1,
+        btreemap! {
+            0 => vec![title],
+            1 => vec![child.clone(), child],
+        },
+    ));
+    let get_node = |d: &D| store.get(d);
+    assert_eq!(validate_document(&test_schema(), &get_node, &root), vec![]);
+}
+
+#[test]
+fn test_validate_violations() {
+    let store = MemoryStore::new();
+    let title = store.put(test_string_node(0, "title"));
+    let wrong_count = store.put(test_string_node(0, "one"));
+    let child = store.put(test_object_node(0, 2, btreemap! { 0 => vec![wrong_count] }));
+    let unknown = store.put(test_object_node(0, 3, btreemap! {}));
+    let missing = D::default();
+    let root = store.put(test_object_node(
+        0,
+        1,
+        btreemap! {
+            0 => vec![title.clone(), title],
+            1 => vec![child, unknown, missing.clone()],
+            7 => vec![],
+        },
+    ));
+    let get_node = |d: &D| store.get(d);
+    let selector = |field_id, index| Selector { field_id, index };
+    assert_eq!(
+        validate_document(&test_schema(), &get_node, &root),
+        vec![
+            (
+                vec![],
+                Violation::TooManyValues {
+                    field_id: 0,
+                    count: 2,
+                }
+            ),
+            (
+                vec![selector(1, 0), selector(0, 0)],
+                Violation::TypeMismatch {
+                    expected: Type::Int,
+                    found: Some(Type::String),
+                }
+            ),
+            (
+                vec![selector(1, 1)],
+                Violation::TypeMismatch {
+                    expected: Type::Object(2),
+                    found: Some(Type::Object(3)),
+                }
+            ),
+            (
+                vec![selector(1, 1)],
+                Violation::UnknownObjectType { object_type_id: 3 }
+            ),
+            (
+                vec![selector(1, 2)],
+                Violation::MissingNode { digest: missing }
+            ),
+            (vec![], Violation::UnknownField { field_id: 7 }),
+        ]
+    );
+}